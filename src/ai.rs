@@ -0,0 +1,250 @@
+use crate::gameplay::{Player, ServeRng};
+use crate::physics::{self, Ball, CourtBounds, Paddle, TimeScale, Velocity, PHYSICS_STAGE};
+use crate::settings::Settings;
+use crate::GameState;
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+//Plugins
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<AiDifficulty>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(
+                    ai_movement
+                        .system()
+                        .label("ai_movement")
+                        .after("handle_inputs")
+                        .after("restart_point"),
+                ),
+            )
+            //The menu's demo is AI against AI
+            .add_system_set(
+                SystemSet::on_update(GameState::MainMenu).with_system(ai_movement.system()),
+            )
+            .add_system_set_to_stage(
+                PHYSICS_STAGE,
+                SystemSet::new()
+                    .with_run_criteria(physics::playing.system())
+                    .with_system(watch_ball.system().after("movement")),
+            );
+    }
+}
+
+//Resources
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum AiDifficulty {
+    Easy,
+    Medium,
+    Hard,
+    Unfair,
+}
+
+//Everything a difficulty changes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AiParams {
+    //How many physics steps old the ball the AI reacts to is
+    pub reaction_delay: usize,
+    //Top tracking speed as a fraction of the paddle speed
+    pub speed_fraction: f32,
+    //Largest aiming mistake, picked fresh for every incoming ball
+    pub aim_error: f32,
+    //Works out where the ball will reach the paddle instead of chasing where it is
+    pub predict: bool,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AiConfig {
+    //Until one is picked from the menu
+    pub difficulty: AiDifficulty,
+}
+
+impl AiDifficulty {
+    pub fn params(self) -> AiParams {
+        match self {
+            AiDifficulty::Easy => AiParams {
+                reaction_delay: 24,
+                speed_fraction: 0.6,
+                aim_error: 30.0,
+                predict: false,
+            },
+            AiDifficulty::Medium => AiParams {
+                reaction_delay: 12,
+                speed_fraction: 0.8,
+                aim_error: 10.0,
+                predict: false,
+            },
+            AiDifficulty::Hard => AiParams {
+                reaction_delay: 6,
+                speed_fraction: 1.0,
+                aim_error: 8.0,
+                predict: true,
+            },
+            AiDifficulty::Unfair => AiParams {
+                reaction_delay: 0,
+                speed_fraction: 1.0,
+                aim_error: 0.0,
+                predict: true,
+            },
+        }
+    }
+}
+
+impl Default for AiDifficulty {
+    fn default() -> Self {
+        AiDifficulty::Medium
+    }
+}
+
+//Components
+pub struct AiControlled {
+    pub params: AiParams,
+    pub dead_zone: f32,
+    //Where along its axis the paddle is heading
+    pub target: f32,
+    pub aim_error: f32,
+    //The ball's position and velocity over the last physics steps, newest last
+    sightings: VecDeque<(Vec2, Vec2)>,
+}
+
+impl AiControlled {
+    //A second of game time is more than any difficulty waits
+    const MAX_SIGHTINGS: usize = 120;
+
+    pub fn new(difficulty: AiDifficulty) -> Self {
+        AiControlled {
+            params: difficulty.params(),
+            dead_zone: 12.0,
+            target: 0.0,
+            aim_error: 0.0,
+            sightings: VecDeque::new(),
+        }
+    }
+
+    //What the AI saw reaction_delay steps of game time ago, so slow motion doesn't sharpen it
+    fn sighting(&self, time_scale: f32) -> Option<(Vec2, Vec2)> {
+        let delay = (self.params.reaction_delay as f32 / time_scale.max(0.01)).round() as usize;
+        let newest = self.sightings.len().checked_sub(1)?;
+        self.sightings.get(newest.saturating_sub(delay)).copied()
+    }
+}
+
+//Helpers
+//Where along the paddle's axis the ball will be once it reaches the paddle, bouncing off the
+//walls on the way. Spin and gravity are left out, so it's only exact for straight lines
+pub fn predict_crossing(
+    position: Vec2,
+    velocity: Vec2,
+    player: Player,
+    paddle: Vec2,
+    bounds: &CourtBounds,
+    radius: f32,
+) -> f32 {
+    let normal = player.goal_normal();
+    let axis = player.axis();
+    let approach = velocity.dot(normal);
+    let time = if approach > 0.0 {
+        ((paddle - position).dot(normal) / approach).max(0.0)
+    } else {
+        0.0
+    };
+    let along = (position + velocity * time).dot(axis);
+
+    //Bouncing between two walls is travelling straight through mirrored copies of the court,
+    //so fold the straight line back into the real one
+    let limit = (bounds.half_size().dot(axis) - radius).max(0.0);
+    if limit == 0.0 {
+        return 0.0;
+    }
+    let folded = (along + limit).rem_euclid(4.0 * limit);
+    if folded <= 2.0 * limit {
+        folded - limit
+    } else {
+        3.0 * limit - folded
+    }
+}
+
+//Systems
+fn watch_ball(
+    balls: Query<(&Transform, &Velocity), With<Ball>>,
+    mut ais: Query<(&Paddle, &Transform, &mut AiControlled), Without<Ball>>,
+) {
+    for (paddle, transform, mut ai) in ais.iter_mut() {
+        //With several balls in play each paddle watches the closest one coming at it, or just
+        //the closest one while none are
+        let position = transform.translation.truncate();
+        let normal = paddle.0.goal_normal();
+        let nearest = |incoming: bool| {
+            balls
+                .iter()
+                .map(|(transform, velocity)| (transform.translation.truncate(), velocity.0))
+                .filter(|(_, velocity)| !incoming || velocity.dot(normal) > 0.0)
+                .min_by(|(a, _), (b, _)| {
+                    a.distance_squared(position)
+                        .partial_cmp(&b.distance_squared(position))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+        };
+        let sighting = match nearest(true).or_else(|| nearest(false)) {
+            Some(sighting) => sighting,
+            None => continue,
+        };
+
+        ai.sightings.push_back(sighting);
+        if ai.sightings.len() > AiControlled::MAX_SIGHTINGS {
+            ai.sightings.pop_front();
+        }
+    }
+}
+
+fn ai_movement(
+    time_scale: Res<TimeScale>,
+    settings: Res<Settings>,
+    bounds: Res<CourtBounds>,
+    mut rng: ResMut<ServeRng>,
+    mut ais: Query<(&Paddle, &Transform, &mut Velocity, &mut AiControlled), Without<Ball>>,
+) {
+    for (paddle, transform, mut velocity, mut ai) in ais.iter_mut() {
+        velocity.0 = Vec2::ZERO;
+        let (ball_pos, ball_velocity) = match ai.sighting(time_scale.0) {
+            Some(sighting) => sighting,
+            None => continue,
+        };
+
+        //Only chase balls headed our way so serves aren't read early
+        let incoming = ball_velocity.dot(paddle.0.goal_normal()) > 0.0;
+        //Pick a fresh aiming mistake for the next incoming ball
+        if !incoming {
+            let error = ai.params.aim_error;
+            ai.aim_error = if error > 0.0 {
+                rng.0.gen_range(-error..=error)
+            } else {
+                0.0
+            };
+            continue;
+        }
+
+        let axis = paddle.0.axis();
+        let position = transform.translation.truncate();
+        let aim = if ai.params.predict {
+            let radius = settings.ball.radius;
+            predict_crossing(ball_pos, ball_velocity, paddle.0, position, &bounds, radius)
+        } else {
+            ball_pos.dot(axis)
+        };
+        ai.target = aim + ai.aim_error;
+
+        let offset = ai.target - position.dot(axis);
+        if offset.abs() > ai.dead_zone {
+            //The side's handicap slows the AI the same way it slows a player
+            let handicap = settings.handicap.get(paddle.0).speed;
+            let max_speed = settings.paddle.speed * handicap * ai.params.speed_fraction;
+            velocity.0 = axis * offset.signum() * max_speed;
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use crate::gameplay::{DemoMode, GoalEvent};
+use crate::input::{Action, KeyBindings};
+use crate::physics::{CollisionEvent, Paddle, WallHitEvent};
+use crate::GameState;
+use bevy::prelude::*;
+use rodio::{OutputStream, OutputStreamHandle, Sink, Source};
+use serde::{Deserialize, Serialize};
+
+//Plugins
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<AudioConfig>()
+            .init_resource::<Volume>()
+            .insert_non_send_resource(SoundOutput::new())
+            .insert_non_send_resource(MusicController::default())
+            .add_startup_system(load_audio.system())
+            .add_system(toggle_mute.system().label("toggle_mute"))
+            .add_system(play_sounds.system().after("toggle_mute"))
+            .add_system(play_goal_sound.system().after("toggle_mute"))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(start_music.system()),
+            )
+            .add_system_set(
+                SystemSet::on_pause(GameState::Playing).with_system(pause_music.system()),
+            )
+            .add_system_set(
+                SystemSet::on_resume(GameState::Playing).with_system(resume_music.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing).with_system(stop_music.system()),
+            );
+    }
+}
+
+//Resources
+pub struct GameSounds {
+    pub paddle_hit: Handle<AudioSource>,
+    pub wall_hit: Handle<AudioSource>,
+    pub goal: Handle<AudioSource>,
+    pub music: Handle<AudioSource>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub sfx_volume: f32,
+    pub music_volume: f32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            sfx_volume: 0.5,
+            music_volume: 0.3,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Volume {
+    pub muted: bool,
+}
+
+impl Volume {
+    pub fn level(&self, volume: f32) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            volume
+        }
+    }
+}
+
+//Sinks aren't Sync, so this lives in a non-send resource
+#[derive(Default)]
+pub struct MusicController {
+    pub sink: Option<Sink>,
+}
+
+//Bevy's Audio can't set a volume per sound, so effects go through their own sinks
+pub struct SoundOutput {
+    stream: Option<(OutputStream, OutputStreamHandle)>,
+}
+
+impl SoundOutput {
+    pub fn new() -> Self {
+        //No audio device just means a silent game
+        let stream = OutputStream::try_default()
+            .map_err(|error| warn!("Audio output unavailable: {}", error))
+            .ok();
+        SoundOutput { stream }
+    }
+
+    pub fn play(&self, source: &AudioSource, volume: f32) {
+        if let Some(sink) = self.sink(volume) {
+            sink.append(source.decoder());
+            sink.detach();
+        }
+    }
+
+    pub fn play_looped(&self, source: &AudioSource, volume: f32) -> Option<Sink> {
+        let sink = self.sink(volume)?;
+        sink.append(source.decoder().repeat_infinite());
+        Some(sink)
+    }
+
+    fn sink(&self, volume: f32) -> Option<Sink> {
+        let (_, handle) = self.stream.as_ref()?;
+        let sink = Sink::try_new(handle).ok()?;
+        sink.set_volume(volume);
+        Some(sink)
+    }
+}
+
+impl Default for SoundOutput {
+    fn default() -> Self {
+        SoundOutput::new()
+    }
+}
+
+//Systems
+fn load_audio(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(GameSounds {
+        paddle_hit: asset_server.load("sounds/paddle_hit.mp3"),
+        wall_hit: asset_server.load("sounds/wall_hit.mp3"),
+        goal: asset_server.load("sounds/goal.mp3"),
+        music: asset_server.load("sounds/music.mp3"),
+    });
+}
+
+fn toggle_mute(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    config: Res<AudioConfig>,
+    mut volume: ResMut<Volume>,
+    music: NonSend<MusicController>,
+) {
+    if !bindings.just_pressed(Action::Mute, &keys) {
+        return;
+    }
+
+    volume.muted = !volume.muted;
+    if let Some(sink) = &music.sink {
+        sink.set_volume(volume.level(config.music_volume));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn play_sounds(
+    output: NonSend<SoundOutput>,
+    sources: Res<Assets<AudioSource>>,
+    sounds: Res<GameSounds>,
+    config: Res<AudioConfig>,
+    volume: Res<Volume>,
+    demo: Option<Res<DemoMode>>,
+    mut collisions: EventReader<CollisionEvent>,
+    mut wall_hits: EventReader<WallHitEvent>,
+    paddles: Query<(), With<Paddle>>,
+) {
+    //Several bounces can land in one frame with the fixed timestep, but each sound only plays once
+    let mut paddle_hit = false;
+    let mut wall_hit = wall_hits.iter().count() > 0;
+    //Anything a ball collides with that isn't a paddle is a block, which sounds like a wall
+    for collision in collisions.iter().filter(|collision| collision.is_impact()) {
+        if paddles.get(collision.entity_b).is_ok() {
+            paddle_hit = true;
+        } else {
+            wall_hit = true;
+        }
+    }
+
+    //The menu's demo plays silently
+    if volume.muted || demo.is_some() {
+        return;
+    }
+
+    let play = |handle: &Handle<AudioSource>| {
+        //Sounds that haven't finished loading are skipped rather than queued
+        if let Some(source) = sources.get(handle) {
+            output.play(source, config.sfx_volume);
+        }
+    };
+
+    if paddle_hit {
+        play(&sounds.paddle_hit);
+    }
+    if wall_hit {
+        play(&sounds.wall_hit);
+    }
+}
+
+fn play_goal_sound(
+    output: NonSend<SoundOutput>,
+    sources: Res<Assets<AudioSource>>,
+    sounds: Res<GameSounds>,
+    config: Res<AudioConfig>,
+    volume: Res<Volume>,
+    mut goals: EventReader<GoalEvent>,
+) {
+    //Multiball goals in the same frame still only play it once
+    let goal = goals.iter().count() > 0;
+    if !goal || volume.muted {
+        return;
+    }
+
+    if let Some(source) = sources.get(&sounds.goal) {
+        output.play(source, config.sfx_volume);
+    }
+}
+
+fn start_music(
+    output: NonSend<SoundOutput>,
+    mut music: NonSendMut<MusicController>,
+    sources: Res<Assets<AudioSource>>,
+    sounds: Res<GameSounds>,
+    config: Res<AudioConfig>,
+    volume: Res<Volume>,
+) {
+    //Keeps trying every frame until the track has loaded
+    if music.sink.is_none() {
+        if let Some(source) = sources.get(&sounds.music) {
+            music.sink = output.play_looped(source, volume.level(config.music_volume));
+        }
+    }
+}
+
+fn pause_music(music: NonSend<MusicController>) {
+    if let Some(sink) = &music.sink {
+        sink.pause();
+    }
+}
+
+fn resume_music(music: NonSend<MusicController>) {
+    if let Some(sink) = &music.sink {
+        sink.play();
+    }
+}
+
+fn stop_music(mut music: NonSendMut<MusicController>) {
+    if let Some(sink) = music.sink.take() {
+        sink.stop();
+    }
+}
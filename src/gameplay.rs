@@ -0,0 +1,1418 @@
+use crate::ai::{AiControlled, AiDifficulty};
+use crate::input::{Action, Inputs, KeyBindings};
+use crate::physics::{
+    snap_interpolation, Ball, BallSpeed, CourtBounds, CourtShrink, Gravity, Interpolated, Obstacle,
+    Paddle, PaddleAccel, PaddleHitEvent, PaddleLength, PaddleSize, SimulationClock, Spin,
+    StepStart, TimeScale, Velocity,
+};
+use crate::powerups::{ActiveEffect, PowerUpKind};
+use crate::settings::{MatchSettings, PaddleConfig, Settings};
+use crate::{despawn_with, GameState};
+use bevy::ecs::component::Component;
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::time::Duration;
+
+//Plugins
+pub struct GameplayPlugin;
+
+impl Plugin for GameplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<MultiBallConfig>()
+            .insert_resource(GameMode::default())
+            .insert_resource(Score::default())
+            .insert_resource(Rally::default())
+            .insert_resource(MatchStats::default())
+            .insert_resource(FastForward::default())
+            .insert_resource(MatchTimer::default())
+            .insert_resource(MatchConfig::from_rules(&MatchSettings::default()))
+            .insert_resource(SetScore::default())
+            .insert_resource(ServeTimer::new(Player::Right))
+            .insert_resource(ServeState::new(Player::Left))
+            .insert_resource(ServeRng(StdRng::from_entropy()))
+            .add_event::<GoalEvent>()
+            .add_event::<RestartEvent>()
+            .add_state(GameState::MainMenu)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_game.system())
+                    .with_system(setup.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing)
+                    .with_system(despawn_with::<InGame>.system())
+                    .with_system(restore_court.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::MainMenu)
+                    .with_system(restore_handicaps.system().before("start_demo"))
+                    .with_system(start_demo.system().label("start_demo")),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::MainMenu).with_system(demo_goal.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::MainMenu)
+                    .with_system(stop_demo.system())
+                    .with_system(despawn_with::<Demo>.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(track_touches.system().label("track_touches"))
+                    .with_system(
+                        goal_detection
+                            .system()
+                            .label("goal_detection")
+                            .after("track_touches"),
+                    )
+                    .with_system(
+                        reset_ball
+                            .system()
+                            .label("reset_ball")
+                            .after("update_score"),
+                    )
+                    .with_system(serve_ball.system().label("serve_ball").after("reset_ball"))
+                    .with_system(
+                        request_restart
+                            .system()
+                            .label("request_restart")
+                            .after("goal_detection"),
+                    )
+                    .with_system(
+                        restart_point
+                            .system()
+                            .label("restart_point")
+                            .after("request_restart")
+                            .after("serve_ball"),
+                    )
+                    .with_system(
+                        spawn_extra_balls
+                            .system()
+                            .after("serve_ball")
+                            .after("restart_point"),
+                    )
+                    .with_system(fast_forward.system().label("fast_forward"))
+                    .with_system(
+                        slow_motion
+                            .system()
+                            .after("serve_ball")
+                            .after("fast_forward"),
+                    )
+                    .with_system(
+                        update_score
+                            .system()
+                            .label("update_score")
+                            .after("goal_detection"),
+                    )
+                    .with_system(score_logger.system().after("update_score"))
+                    .with_system(count_rally.system().after("goal_detection"))
+                    .with_system(track_match_stats.system().after("goal_detection"))
+                    .with_system(shrink_paddles.system().after("goal_detection"))
+                    .with_system(
+                        check_winner
+                            .system()
+                            .label("check_winner")
+                            .after("update_score"),
+                    )
+                    .with_system(
+                        run_match_clock
+                            .system()
+                            .after("update_score")
+                            .after("serve_ball"),
+                    ),
+            );
+    }
+}
+
+//Resources
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum GameMode {
+    TwoPlayer,
+    //Against the computer, named for the side the human plays
+    VsAiLeft,
+    VsAiRight,
+    AiVsAi,
+    FourPlayer,
+    Practice,
+}
+
+//How a ball leaving the court turns into a point
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scoring {
+    //The player across from the goal it went into
+    Opponent,
+    //Whoever touched it last, unless that's the player who let it in
+    LastTouch,
+    //Nobody scores, a goal just ends the rally
+    Rally,
+}
+
+impl GameMode {
+    pub fn players(self) -> &'static [Player] {
+        match self {
+            GameMode::FourPlayer => &Player::ALL,
+            GameMode::TwoPlayer | GameMode::VsAiLeft | GameMode::VsAiRight | GameMode::AiVsAi => {
+                &[Player::Left, Player::Right]
+            }
+            //The right side is left to the wall
+            GameMode::Practice => &[Player::Left],
+        }
+    }
+
+    pub fn scoring(self) -> Scoring {
+        match self {
+            GameMode::FourPlayer => Scoring::LastTouch,
+            GameMode::TwoPlayer | GameMode::VsAiLeft | GameMode::VsAiRight | GameMode::AiVsAi => {
+                Scoring::Opponent
+            }
+            GameMode::Practice => Scoring::Rally,
+        }
+    }
+
+    //Paddles the computer plays, input from keys and gamepads never reaches them
+    pub fn ai_players(self) -> &'static [Player] {
+        match self {
+            GameMode::VsAiLeft => &[Player::Right],
+            GameMode::VsAiRight => &[Player::Left],
+            GameMode::AiVsAi => &[Player::Left, Player::Right],
+            _ => &[],
+        }
+    }
+
+    pub fn scorer(self, conceder: Player, last_touch: Option<Player>) -> Player {
+        match (self.scoring(), last_touch) {
+            (Scoring::LastTouch, Some(player)) if player != conceder => player,
+            //Own goals and untouched balls still count for the side across the court
+            _ => conceder.opponent(),
+        }
+    }
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::TwoPlayer
+    }
+}
+
+#[derive(Default)]
+pub struct Score {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+impl Score {
+    //The two best scores, the second being the closest challenger's
+    pub fn top_two(&self, players: &[Player]) -> (u32, u32) {
+        let mut points: Vec<u32> = players.iter().map(|player| self.points(*player)).collect();
+        points.sort_unstable_by(|a, b| b.cmp(a));
+        (points[0], points.get(1).copied().unwrap_or(0))
+    }
+
+    pub fn points(&self, player: Player) -> u32 {
+        match player {
+            Player::Left => self.left,
+            Player::Right => self.right,
+            Player::Top => self.top,
+            Player::Bottom => self.bottom,
+        }
+    }
+
+    pub fn points_mut(&mut self, player: Player) -> &mut u32 {
+        match player {
+            Player::Left => &mut self.left,
+            Player::Right => &mut self.right,
+            Player::Top => &mut self.top,
+            Player::Bottom => &mut self.bottom,
+        }
+    }
+
+    //Ties go to whoever comes last in players
+    pub fn leader(&self, players: &[Player]) -> Player {
+        players
+            .iter()
+            .copied()
+            .max_by_key(|player| self.points(*player))
+            .unwrap_or(Player::Left)
+    }
+}
+
+//How a match is won, taken from the settings when it starts
+#[derive(Clone, Copy)]
+pub struct MatchConfig {
+    pub sets_to_win: u32,
+    pub points_per_set: u32,
+}
+
+impl MatchConfig {
+    pub fn from_rules(rules: &MatchSettings) -> Self {
+        MatchConfig {
+            sets_to_win: rules.sets_to_win.max(1),
+            points_per_set: rules.points_to_win,
+        }
+    }
+
+    pub fn has_sets(&self, mode: GameMode) -> bool {
+        //Four player matches are a single set, the tally only has the two sides
+        self.sets_to_win > 1 && mode.players() == [Player::Left, Player::Right]
+    }
+
+    //A timed match can end with the set being played led by whoever is behind on sets
+    pub fn winner(&self, mode: GameMode, score: &Score, set_score: &SetScore) -> Player {
+        match set_score.leader() {
+            Some(leader) if self.has_sets(mode) => leader,
+            _ => score.leader(mode.players()),
+        }
+    }
+}
+
+//Sets taken so far, and the left and right points each finished set ended on
+#[derive(Default)]
+pub struct SetScore {
+    pub left_sets: u32,
+    pub right_sets: u32,
+    pub sets: Vec<(u32, u32)>,
+}
+
+impl SetScore {
+    pub fn sets(&self, player: Player) -> u32 {
+        match player {
+            Player::Left => self.left_sets,
+            Player::Right => self.right_sets,
+            Player::Top | Player::Bottom => 0,
+        }
+    }
+
+    //Whoever is ahead on sets, if anyone
+    pub fn leader(&self) -> Option<Player> {
+        match self.left_sets.cmp(&self.right_sets) {
+            Ordering::Greater => Some(Player::Left),
+            Ordering::Less => Some(Player::Right),
+            Ordering::Equal => None,
+        }
+    }
+
+    fn award(&mut self, winner: Player, score: &Score) {
+        match winner {
+            Player::Left => self.left_sets += 1,
+            Player::Right => self.right_sets += 1,
+            Player::Top | Player::Bottom => {}
+        }
+        self.sets.push((score.left, score.right));
+    }
+}
+
+//Paddle hits since the ball last got past, practice mode's score
+#[derive(Default)]
+pub struct Rally {
+    pub current: u32,
+    pub best: u32,
+}
+
+//Shown on the game over screen, a rally here counts every ball's hits between two goals
+#[derive(Default)]
+pub struct MatchStats {
+    pub longest_rally: u32,
+    pub paddle_hits: u32,
+    pub peak_speed: f32,
+    rally: u32,
+}
+
+//Speed an AI vs AI match runs at when nothing is slowing it down
+pub struct FastForward(pub f32);
+
+impl FastForward {
+    pub const SPEEDS: [f32; 3] = [1.0, 2.0, 4.0];
+}
+
+impl Default for FastForward {
+    fn default() -> Self {
+        FastForward(1.0)
+    }
+}
+
+//Time left in a timed match, it only runs while the ball is in play
+pub struct MatchTimer {
+    //None when the match is played to points alone
+    pub clock: Option<Timer>,
+    //Time ran out with the scores level, the next goal wins
+    pub sudden_death: bool,
+    shrink: Timer,
+}
+
+impl MatchTimer {
+    //Every SHRINK_INTERVAL seconds of sudden death takes SHRINK off the court's size
+    const SHRINK_INTERVAL: f32 = 15.0;
+    const SHRINK: f32 = 0.1;
+
+    pub fn new(minutes: f32) -> Self {
+        let clock = if minutes > 0.0 {
+            Some(Timer::from_seconds(minutes * 60.0, false))
+        } else {
+            None
+        };
+        MatchTimer {
+            clock,
+            sudden_death: false,
+            shrink: Timer::from_seconds(Self::SHRINK_INTERVAL, true),
+        }
+    }
+
+    pub fn remaining(&self) -> Option<Duration> {
+        self.clock
+            .as_ref()
+            .map(|clock| clock.duration().saturating_sub(clock.elapsed()))
+    }
+}
+
+impl Default for MatchTimer {
+    fn default() -> Self {
+        MatchTimer::new(0.0)
+    }
+}
+
+pub struct ServeTimer {
+    pub timer: Timer,
+    pub receiver: Player,
+}
+
+impl ServeTimer {
+    pub fn new(receiver: Player) -> Self {
+        ServeTimer {
+            timer: Timer::from_seconds(3.0, false),
+            receiver,
+        }
+    }
+}
+
+//Who the next serve comes from, the ball always goes to the player across from them
+pub struct ServeState {
+    pub server: Player,
+    //Serves taken in a row by the current server
+    pub serves: u32,
+}
+
+impl ServeState {
+    pub fn new(server: Player) -> Self {
+        ServeState { server, serves: 0 }
+    }
+
+    pub fn receiver(&self) -> Player {
+        self.server.opponent()
+    }
+
+    //Hands the serve on along the mode's players once the server has had their turn
+    fn next_serve(&mut self, players: &[Player], deuce: bool) {
+        self.serves += 1;
+        let turn = if deuce { 1 } else { 2 };
+        if self.serves >= turn {
+            let index = players.iter().position(|player| *player == self.server);
+            let next = index.map_or(0, |index| (index + 1) % players.len());
+            self.server = players[next];
+            self.serves = 0;
+        }
+    }
+}
+
+//Who serves after a goal
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ServeRule {
+    //The player who conceded gets the ball, like classic pong
+    Loser,
+    //Table tennis: two serves each, one each at deuce, and a two point lead to win
+    Alternate,
+}
+
+impl Default for ServeRule {
+    fn default() -> Self {
+        ServeRule::Loser
+    }
+}
+
+pub struct ServeRng(pub StdRng);
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MultiBallConfig {
+    pub count: usize,
+    //Seconds into a rally between extra balls, 0 turns chaos mode off
+    pub spawn_interval: f32,
+}
+
+impl MultiBallConfig {
+    pub const MAX_BALLS: usize = 4;
+
+    pub fn balls(&self) -> usize {
+        self.count.clamp(1, Self::MAX_BALLS)
+    }
+}
+
+impl Default for MultiBallConfig {
+    fn default() -> Self {
+        MultiBallConfig {
+            count: 1,
+            spawn_interval: 0.0,
+        }
+    }
+}
+
+//Multipliers on a paddle's top speed and length, below 1 makes the player's job harder
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Handicap {
+    pub speed: f32,
+    pub height: f32,
+}
+
+impl Handicap {
+    pub const MIN: f32 = 0.5;
+    pub const MAX: f32 = 2.0;
+    pub const STEP: f32 = 0.1;
+
+    pub fn is_active(&self) -> bool {
+        self.speed != 1.0 || self.height != 1.0
+    }
+
+    //The game over screen steps both together, the settings file can set them apart
+    pub fn adjust(&mut self, steps: f32) {
+        let adjust =
+            |value: f32| (value + steps * Handicap::STEP).clamp(Handicap::MIN, Handicap::MAX);
+        self.speed = adjust(self.speed);
+        self.height = adjust(self.height);
+    }
+}
+
+impl Default for Handicap {
+    fn default() -> Self {
+        Handicap {
+            speed: 1.0,
+            height: 1.0,
+        }
+    }
+}
+
+//Only the two side players can be handicapped
+#[derive(Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HandicapConfig {
+    pub left: Handicap,
+    pub right: Handicap,
+}
+
+impl HandicapConfig {
+    pub fn get(&self, player: Player) -> Handicap {
+        match player {
+            Player::Left => self.left,
+            Player::Right => self.right,
+            Player::Top | Player::Bottom => Handicap::default(),
+        }
+    }
+
+    pub fn get_mut(&mut self, player: Player) -> Option<&mut Handicap> {
+        match player {
+            Player::Left => Some(&mut self.left),
+            Player::Right => Some(&mut self.right),
+            Player::Top | Player::Bottom => None,
+        }
+    }
+}
+
+//The handicaps from the settings file, put back whenever the menu comes up
+pub struct SavedHandicaps(pub HandicapConfig);
+
+//Present while the menu plays itself in the background
+pub struct DemoMode;
+
+//Events
+pub struct GoalEvent {
+    pub scorer: Player,
+    pub conceder: Player,
+    //Where the ball was and how it was moving when it was found out of the court
+    pub ball_exit_x: f32,
+    pub ball_exit_y: f32,
+    pub ball_exit_velocity: Vec2,
+}
+
+//The current point starts over without anyone scoring
+pub struct RestartEvent;
+
+//Components
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Player {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Player {
+    pub const ALL: [Player; 4] = [Player::Left, Player::Right, Player::Top, Player::Bottom];
+
+    //Single letter for labels, e.g. the score prefixes in colorblind mode
+    pub fn initial(self) -> char {
+        match self {
+            Player::Left => 'L',
+            Player::Right => 'R',
+            Player::Top => 'T',
+            Player::Bottom => 'B',
+        }
+    }
+
+    pub fn opponent(self) -> Player {
+        match self {
+            Player::Left => Player::Right,
+            Player::Right => Player::Left,
+            Player::Top => Player::Bottom,
+            Player::Bottom => Player::Top,
+        }
+    }
+
+    //Points out of the court through this player's goal
+    pub fn goal_normal(self) -> Vec2 {
+        match self {
+            Player::Left => -Vec2::X,
+            Player::Right => Vec2::X,
+            Player::Top => Vec2::Y,
+            Player::Bottom => -Vec2::Y,
+        }
+    }
+
+    //The direction this player's paddle slides along
+    pub fn axis(self) -> Vec2 {
+        match self {
+            Player::Left | Player::Right => Vec2::Y,
+            Player::Top | Player::Bottom => Vec2::X,
+        }
+    }
+}
+
+//The paddle that last returned this ball
+pub struct LastTouch(pub Option<Player>);
+
+#[derive(Clone)]
+pub struct InGame;
+#[derive(Clone)]
+pub struct Demo;
+
+//Helpers
+//Spawns the ball and both paddles tagged with marker, returning (ball, left, right)
+fn spawn_pieces<T: Component + Clone>(
+    marker: T,
+    settings: &Settings,
+    bounds: &CourtBounds,
+    commands: &mut Commands,
+) -> (Entity, Entity, Entity) {
+    //Only the simulation state, the graphics plugin attaches shapes if present
+    let ball = spawn_ball(marker.clone(), settings, commands);
+
+    let paddle_left = spawn_paddle(settings, bounds, Player::Left, commands);
+    let paddle_right = spawn_paddle(settings, bounds, Player::Right, commands);
+
+    commands.entity(paddle_left).insert(marker.clone());
+    commands.entity(paddle_right).insert(marker);
+    (ball, paddle_left, paddle_right)
+}
+
+fn spawn_ball<T: Component>(marker: T, settings: &Settings, commands: &mut Commands) -> Entity {
+    commands
+        .spawn_bundle((Transform::default(), GlobalTransform::default()))
+        .insert(marker)
+        .insert(Ball)
+        .insert(BallSpeed(settings.ball.base_speed))
+        .insert(Velocity(Vec2::ZERO))
+        .insert(Spin(0.0))
+        .insert(StepStart {
+            position: Vec2::ZERO,
+            time: 0.0,
+        })
+        .insert(LastTouch(None))
+        .id()
+}
+
+fn spawn_paddle(
+    settings: &Settings,
+    bounds: &CourtBounds,
+    player: Player,
+    commands: &mut Commands,
+) -> Entity {
+    let config = &settings.paddle;
+    let position = bounds.paddle_position(player, config.edge_offset);
+    let length = config.height * settings.handicap.get(player).height;
+    commands
+        .spawn_bundle((
+            Transform::from_translation(position.extend(0.0)),
+            GlobalTransform::default(),
+        ))
+        .insert(Paddle(player))
+        .insert(PaddleSize(paddle_size(config, player, length)))
+        .insert(PaddleLength(length))
+        .insert(PaddleAccel {
+            acceleration: config.acceleration,
+            friction: config.friction,
+        })
+        .insert(Velocity(Vec2::ZERO))
+        .id()
+}
+
+//Paddles are always config.width thick, length runs along the paddle's axis
+pub fn paddle_size(config: &PaddleConfig, player: Player, length: f32) -> Vec2 {
+    player.axis() * length + player.goal_normal().abs() * config.width
+}
+
+//The first paddle whose lane, the strip it slides along, a block at center would cut into
+fn blocked_lane(
+    settings: &Settings,
+    bounds: &CourtBounds,
+    mode: GameMode,
+    center: Vec2,
+    size: Vec2,
+) -> Option<Player> {
+    mode.players().iter().copied().find(|player| {
+        let normal = player.goal_normal().abs();
+        let lane = bounds
+            .paddle_position(*player, settings.paddle.edge_offset)
+            .dot(normal);
+        let reach = (size.dot(normal) + settings.paddle.width) / 2.0;
+        (center.dot(normal) - lane).abs() < reach
+    })
+}
+
+//Fresh balls at center waiting on the receiver's serve, shared by goals and restarts
+fn reset_point(
+    receiver: Player,
+    settings: &Settings,
+    multiball: &MultiBallConfig,
+    balls: impl Iterator<Item = Entity>,
+    serve_timer: &mut ServeTimer,
+    commands: &mut Commands,
+) -> Vec<Entity> {
+    *serve_timer = ServeTimer::new(receiver);
+    for entity in balls {
+        commands.entity(entity).despawn();
+    }
+    (0..multiball.balls())
+        .map(|_| spawn_ball(InGame, settings, commands))
+        .collect()
+}
+
+fn serve_velocity(receiver: Player, speed: f32, gravity: Vec2, rng: &mut impl Rng) -> Vec2 {
+    const MIN_SERVE_ANGLE: f32 = 10.0;
+    const MAX_SERVE_ANGLE: f32 = 60.0;
+
+    //Never straight at the goal, never too steep
+    let mut angle = rng
+        .gen_range(MIN_SERVE_ANGLE..=MAX_SERVE_ANGLE)
+        .to_radians();
+    if rng.gen::<bool>() {
+        angle = -angle;
+    }
+    //Under gravity the serve goes up against the pull so it arcs over rather than diving into a wall
+    let mut along = receiver.axis() * angle.sin();
+    if along.dot(gravity) > 0.0 {
+        along = -along;
+    }
+    (receiver.goal_normal() * angle.cos() + along) * speed
+}
+
+//Players who lose the match if they concede the next goal, only in the set that can decide it
+fn facing_match_point(
+    score: &Score,
+    mode: GameMode,
+    rules: &MatchSettings,
+    config: &MatchConfig,
+    set_score: &SetScore,
+) -> Vec<Player> {
+    if mode.scoring() == Scoring::Rally {
+        return Vec::new();
+    }
+
+    let players = mode.players();
+    let last_set =
+        |player: Player| !config.has_sets(mode) || set_score.sets(player) + 1 >= config.sets_to_win;
+    let on_match_point = |player: Player| {
+        let challenger = players
+            .iter()
+            .filter(|other| **other != player)
+            .map(|other| score.points(*other))
+            .max()
+            .unwrap_or(0);
+        last_set(player)
+            && rules.reaches(config.points_per_set, score.points(player) + 1, challenger)
+    };
+    players
+        .iter()
+        .copied()
+        .filter(|defender| {
+            players
+                .iter()
+                .any(|player| player != defender && on_match_point(*player))
+        })
+        .collect()
+}
+
+//Systems
+#[allow(clippy::too_many_arguments)]
+fn reset_game(
+    mut score: ResMut<Score>,
+    mut serve_timer: ResMut<ServeTimer>,
+    mut serve_state: ResMut<ServeState>,
+    mut inputs: ResMut<Inputs>,
+    mut rally: ResMut<Rally>,
+    mut stats: ResMut<MatchStats>,
+    mut time_scale: ResMut<TimeScale>,
+    mut fast_forward: ResMut<FastForward>,
+    settings: Res<Settings>,
+    mut match_timer: ResMut<MatchTimer>,
+    mut shrink: ResMut<CourtShrink>,
+    mut match_config: ResMut<MatchConfig>,
+    mut set_score: ResMut<SetScore>,
+) {
+    *score = Score::default();
+    *match_config = MatchConfig::from_rules(&settings.rules);
+    *set_score = SetScore::default();
+    *match_timer = MatchTimer::new(settings.rules.time_limit);
+    *shrink = CourtShrink::default();
+    *stats = MatchStats::default();
+    *fast_forward = FastForward::default();
+    //The best rally is kept for the whole session
+    rally.current = 0;
+    *serve_state = ServeState::new(Player::Left);
+    *serve_timer = ServeTimer::new(serve_state.receiver());
+    *inputs = Inputs::default();
+    *time_scale = TimeScale::default();
+}
+
+fn setup(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    bounds: Res<CourtBounds>,
+    mode: Res<GameMode>,
+    difficulty: Res<AiDifficulty>,
+    multiball: Res<MultiBallConfig>,
+) {
+    for _ in 0..multiball.balls() {
+        spawn_ball(InGame, &settings, &mut commands);
+    }
+
+    //Sides without a player are walls
+    for &player in mode.players() {
+        let paddle = spawn_paddle(&settings, &bounds, player, &mut commands);
+        commands.entity(paddle).insert(InGame);
+        if mode.ai_players().contains(&player) {
+            commands
+                .entity(paddle)
+                .insert(AiControlled::new(*difficulty));
+        }
+    }
+
+    let obstacle = &settings.obstacle;
+    if obstacle.arcade {
+        for block in &obstacle.layout {
+            let center = Vec2::new(block.x, block.y);
+            let size = Vec2::new(block.width, block.height);
+            if let Some(player) = blocked_lane(&settings, &bounds, *mode, center, size) {
+                warn!(
+                    "Skipping an obstacle at {} in the path of the {:?} paddle",
+                    center, player
+                );
+                continue;
+            }
+            commands
+                .spawn_bundle((
+                    Transform::from_translation(center.extend(0.0)),
+                    GlobalTransform::default(),
+                ))
+                .insert(InGame)
+                .insert(Obstacle { size });
+        }
+    } else if obstacle.enabled {
+        commands
+            .spawn_bundle((Transform::default(), GlobalTransform::default()))
+            .insert(InGame)
+            .insert(Obstacle {
+                size: Vec2::new(obstacle.width, obstacle.height),
+            })
+            .insert(Velocity(Vec2::new(0.0, obstacle.speed)));
+    }
+}
+
+//Sudden death's shrunken court goes back to full size once the match is over
+fn restore_court(mut shrink: ResMut<CourtShrink>) {
+    *shrink = CourtShrink::default();
+}
+
+//Handicaps changed between rematches only last until the players leave for the menu
+fn restore_handicaps(saved: Option<Res<SavedHandicaps>>, mut settings: ResMut<Settings>) {
+    if let Some(saved) = saved {
+        settings.handicap = saved.0;
+    }
+}
+
+fn start_demo(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    bounds: Res<CourtBounds>,
+    gravity: Res<Gravity>,
+    mut rng: ResMut<ServeRng>,
+) {
+    commands.insert_resource(DemoMode);
+    //A match left in slow motion shouldn't slow the demo down
+    commands.insert_resource(TimeScale::default());
+
+    let (ball, paddle_left, paddle_right) = spawn_pieces(Demo, &settings, &bounds, &mut commands);
+    commands.entity(ball).insert(Velocity(serve_velocity(
+        Player::Right,
+        settings.ball.base_speed,
+        gravity.0,
+        &mut rng.0,
+    )));
+
+    //Slightly different reflexes on each side so rallies eventually end
+    for paddle in [paddle_left, paddle_right] {
+        let mut ai = AiControlled::new(AiDifficulty::Medium);
+        ai.params.reaction_delay = rng.0.gen_range(8..=16);
+        commands.entity(paddle).insert(ai);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn demo_goal(
+    settings: Res<Settings>,
+    bounds: Res<CourtBounds>,
+    gravity: Res<Gravity>,
+    mut rng: ResMut<ServeRng>,
+    mut query: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &mut BallSpeed,
+            &mut Spin,
+            Option<&mut Interpolated>,
+        ),
+        With<Demo>,
+    >,
+) {
+    //No score in the demo, the ball just goes straight back into play
+    let limit = bounds.half_width + settings.ball.radius;
+    for (mut transform, mut velocity, mut speed, mut spin, interpolated) in query.iter_mut() {
+        if transform.translation.x.abs() <= limit {
+            continue;
+        }
+
+        let receiver = if transform.translation.x < 0.0 {
+            Player::Left
+        } else {
+            Player::Right
+        };
+        transform.translation = Vec3::ZERO;
+        snap_interpolation(&transform, interpolated);
+        speed.0 = settings.ball.base_speed;
+        spin.0 = 0.0;
+        velocity.0 = serve_velocity(receiver, speed.0, gravity.0, &mut rng.0);
+    }
+}
+
+fn stop_demo(mut commands: Commands) {
+    commands.remove_resource::<DemoMode>();
+}
+
+fn track_touches(mut paddle_hits: EventReader<PaddleHitEvent>, mut query: Query<&mut LastTouch>) {
+    for hit in paddle_hits.iter() {
+        if let Ok(mut touch) = query.get_mut(hit.ball) {
+            touch.0 = Some(hit.player);
+        }
+    }
+}
+
+pub fn goal_detection(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    bounds: Res<CourtBounds>,
+    mode: Res<GameMode>,
+    mut goals: EventWriter<GoalEvent>,
+    query: Query<(Entity, &Transform, &Velocity, &LastTouch), With<Ball>>,
+) {
+    let limit = bounds.half_size() + Vec2::splat(settings.ball.radius);
+    let mut balls = 0;
+    let mut out = Vec::new();
+    for (entity, transform, velocity, touch) in query.iter() {
+        balls += 1;
+        //Only sides with a paddle have a goal, the others are walls
+        let position = transform.translation.truncate();
+        let conceder = mode.players().iter().copied().find(|player| {
+            let normal = player.goal_normal();
+            position.dot(normal) > limit.dot(normal.abs())
+        });
+        if let Some(conceder) = conceder {
+            let scorer = mode.scorer(conceder, touch.0);
+            out.push((
+                entity,
+                GoalEvent {
+                    scorer,
+                    conceder,
+                    ball_exit_x: position.x,
+                    ball_exit_y: position.y,
+                    ball_exit_velocity: velocity.0,
+                },
+            ));
+        }
+    }
+
+    //With several balls in play only the last one out scores, the rest just leave
+    let scoring = if out.len() == balls { out.pop() } else { None };
+    for (entity, _) in out {
+        commands.entity(entity).despawn();
+    }
+    if let Some((_, goal)) = scoring {
+        goals.send(goal);
+    }
+}
+
+pub fn update_score(
+    mode: Res<GameMode>,
+    mut goals: EventReader<GoalEvent>,
+    mut score: ResMut<Score>,
+) {
+    if mode.scoring() == Scoring::Rally {
+        return;
+    }
+
+    for goal in goals.iter() {
+        *score.points_mut(goal.scorer) += 1;
+    }
+}
+
+fn count_rally(
+    mode: Res<GameMode>,
+    mut paddle_hits: EventReader<PaddleHitEvent>,
+    mut goals: EventReader<GoalEvent>,
+    mut rally: ResMut<Rally>,
+) {
+    if mode.scoring() != Scoring::Rally {
+        return;
+    }
+
+    for _ in paddle_hits.iter() {
+        rally.current += 1;
+        rally.best = rally.best.max(rally.current);
+    }
+    if goals.iter().count() > 0 {
+        rally.current = 0;
+    }
+}
+
+fn track_match_stats(
+    mut paddle_hits: EventReader<PaddleHitEvent>,
+    mut goals: EventReader<GoalEvent>,
+    mut stats: ResMut<MatchStats>,
+    balls: Query<&Velocity, With<Ball>>,
+) {
+    for _ in paddle_hits.iter() {
+        stats.paddle_hits += 1;
+        stats.rally += 1;
+        stats.longest_rally = stats.longest_rally.max(stats.rally);
+    }
+    if goals.iter().count() > 0 {
+        stats.rally = 0;
+    }
+
+    //Measured off the actual velocity so power-ups, spin and gravity all count
+    for velocity in balls.iter() {
+        stats.peak_speed = stats.peak_speed.max(velocity.0.length());
+    }
+}
+
+pub fn shrink_paddles(
+    settings: Res<Settings>,
+    mut goals: EventReader<GoalEvent>,
+    mut query: Query<(&Paddle, &mut PaddleLength, &mut PaddleSize)>,
+) {
+    const SHRINK: f32 = 6.0;
+    const MIN_LENGTH: f32 = 20.0;
+
+    if !settings.rules.shrinking_paddles {
+        return;
+    }
+
+    for goal in goals.iter() {
+        for (paddle, mut length, mut size) in query.iter_mut() {
+            if paddle.0 != goal.conceder {
+                continue;
+            }
+            //Never grows a paddle that already started out shorter than the minimum
+            let shrunk = (length.0 - SHRINK).max(MIN_LENGTH.min(length.0));
+            //Scaling the current size keeps any paddle power-up in effect
+            let current = size.0.dot(paddle.0.axis());
+            size.0 = paddle_size(&settings.paddle, paddle.0, current * shrunk / length.0);
+            length.0 = shrunk;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_extra_balls(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    settings: Res<Settings>,
+    multiball: Res<MultiBallConfig>,
+    serve_timer: Res<ServeTimer>,
+    mode: Res<GameMode>,
+    gravity: Res<Gravity>,
+    mut rng: ResMut<ServeRng>,
+    mut rally: Local<Timer>,
+    query: Query<(), With<Ball>>,
+) {
+    if multiball.spawn_interval <= 0.0 {
+        return;
+    }
+
+    //The clock only runs once the ball is in play and starts over with every serve
+    if !serve_timer.timer.finished() {
+        *rally = Timer::from_seconds(multiball.spawn_interval, true);
+        return;
+    }
+    if !rally.tick(clock.delta()).just_finished() {
+        return;
+    }
+
+    if query.iter().count() < MultiBallConfig::MAX_BALLS {
+        let players = mode.players();
+        let receiver = players[rng.0.gen_range(0..players.len())];
+        let velocity = serve_velocity(receiver, settings.ball.base_speed, gravity.0, &mut rng.0);
+        let ball = spawn_ball(InGame, &settings, &mut commands);
+        commands.entity(ball).insert(Velocity(velocity));
+    }
+}
+
+fn fast_forward(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mode: Res<GameMode>,
+    mut fast_forward: ResMut<FastForward>,
+) {
+    //Only for watching, a human couldn't keep up
+    if *mode != GameMode::AiVsAi || !bindings.just_pressed(Action::FastForward, &keys) {
+        return;
+    }
+
+    let speeds = FastForward::SPEEDS;
+    let current = speeds.iter().position(|speed| *speed == fast_forward.0);
+    fast_forward.0 = speeds[current.map_or(0, |index| (index + 1) % speeds.len())];
+}
+
+#[allow(clippy::too_many_arguments)]
+fn slow_motion(
+    clock: Res<SimulationClock>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    match_config: Res<MatchConfig>,
+    set_score: Res<SetScore>,
+    serve_timer: Res<ServeTimer>,
+    fast_forward: Res<FastForward>,
+    effects: Query<&ActiveEffect, With<Ball>>,
+    balls: Query<&Transform, With<Ball>>,
+    paddles: Query<(&Paddle, &Transform)>,
+    mut time_scale: ResMut<TimeScale>,
+) {
+    const SLOW_SCALE: f32 = 0.3;
+    const EASE_SECONDS: f32 = 0.5;
+    const MATCH_POINT_SCALE: f32 = 0.6;
+    const MATCH_POINT_RANGE: f32 = 100.0;
+    const MATCH_POINT_RAMP: f32 = 0.3;
+
+    //Slow motion is relative to whatever speed the match is running at
+    let full = fast_forward.0;
+    if time_scale.0 > full {
+        time_scale.0 = full;
+    }
+
+    //Shares the serve key by default, so it only kicks in once the ball is in play
+    let held = serve_timer.timer.finished() && bindings.pressed(Action::SlowMotion, &keys);
+    let slow_ball = effects
+        .iter()
+        .any(|effect| effect.kind == PowerUpKind::SlowBall);
+
+    let defenders = if settings.rules.match_point_slow_motion && serve_timer.timer.finished() {
+        facing_match_point(&score, *mode, &settings.rules, &match_config, &set_score)
+    } else {
+        Vec::new()
+    };
+    //Measured along the goal axis, so only how close the ball is to the line matters
+    let tense = paddles
+        .iter()
+        .filter(|(paddle, _)| defenders.contains(&paddle.0))
+        .any(|(paddle, paddle_transform)| {
+            let normal = paddle.0.goal_normal();
+            balls.iter().any(|ball| {
+                let gap = (ball.translation - paddle_transform.translation).truncate();
+                gap.dot(normal).abs() < MATCH_POINT_RANGE
+            })
+        });
+    let target = if tense {
+        MATCH_POINT_SCALE * full
+    } else {
+        full
+    };
+
+    if held || slow_ball {
+        time_scale.0 = SLOW_SCALE * full;
+    } else if time_scale.0 > target {
+        //Eases into the match point slow down rather than jumping to it
+        let step = (1.0 - MATCH_POINT_SCALE) * full / MATCH_POINT_RAMP * clock.delta_seconds();
+        time_scale.0 = (time_scale.0 - step).max(target);
+    } else {
+        //Lerps back up to full speed over EASE_SECONDS instead of snapping
+        let step = (1.0 - SLOW_SCALE) * full / EASE_SECONDS * clock.delta_seconds();
+        time_scale.0 = (time_scale.0 + step).min(target);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn reset_ball(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    multiball: Res<MultiBallConfig>,
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    mut goals: EventReader<GoalEvent>,
+    mut serve_state: ResMut<ServeState>,
+    mut serve_timer: ResMut<ServeTimer>,
+    query: Query<Entity, With<Ball>>,
+) {
+    if let Some(goal) = goals.iter().last() {
+        //Practice has nobody to alternate with
+        if settings.rules.serving == ServeRule::Alternate && mode.scoring() != Scoring::Rally {
+            let (_, trailer) = score.top_two(mode.players());
+            let deuce = trailer + 1 >= settings.rules.points_to_win;
+            serve_state.next_serve(mode.players(), deuce);
+        } else {
+            *serve_state = ServeState::new(goal.conceder.opponent());
+        }
+
+        reset_point(
+            serve_state.receiver(),
+            &settings,
+            &multiball,
+            query.iter(),
+            &mut serve_timer,
+            &mut commands,
+        );
+    }
+}
+
+fn request_restart(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut goals: EventReader<GoalEvent>,
+    mut restarts: EventWriter<RestartEvent>,
+) {
+    //A goal this frame is already resetting the point
+    if bindings.just_pressed(Action::Restart, &keys) && goals.iter().count() == 0 {
+        restarts.send(RestartEvent);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn restart_point(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    multiball: Res<MultiBallConfig>,
+    bounds: Res<CourtBounds>,
+    gravity: Res<Gravity>,
+    mut rng: ResMut<ServeRng>,
+    mut serve_timer: ResMut<ServeTimer>,
+    mut rally: ResMut<Rally>,
+    mut time_scale: ResMut<TimeScale>,
+    mut restarts: EventReader<RestartEvent>,
+    balls: Query<Entity, With<Ball>>,
+    mut paddles: Query<(
+        &Paddle,
+        &mut Transform,
+        &mut Velocity,
+        Option<&mut Interpolated>,
+    )>,
+) {
+    if restarts.iter().count() == 0 {
+        return;
+    }
+
+    let receiver = serve_timer.receiver;
+    let fresh = reset_point(
+        receiver,
+        &settings,
+        &multiball,
+        balls.iter(),
+        &mut serve_timer,
+        &mut commands,
+    );
+    //No countdown, the new balls go straight into play
+    let duration = serve_timer.timer.duration();
+    serve_timer.timer.tick(duration);
+    for ball in fresh {
+        let velocity = serve_velocity(receiver, settings.ball.base_speed, gravity.0, &mut rng.0);
+        commands.entity(ball).insert(Velocity(velocity));
+    }
+
+    for (paddle, mut transform, mut velocity, interpolated) in paddles.iter_mut() {
+        let position = bounds.paddle_position(paddle.0, settings.paddle.edge_offset);
+        transform.translation = position.extend(transform.translation.z);
+        snap_interpolation(&transform, interpolated);
+        velocity.0 = Vec2::ZERO;
+    }
+    rally.current = 0;
+    *time_scale = TimeScale::default();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn serve_ball(
+    clock: Res<SimulationClock>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    settings: Res<Settings>,
+    gravity: Res<Gravity>,
+    mut rng: ResMut<ServeRng>,
+    mut serve_timer: ResMut<ServeTimer>,
+    mut query: Query<(&mut Velocity, &mut BallSpeed)>,
+) {
+    //Serving early skips the rest of the countdown
+    let delta = if bindings.just_pressed(Action::Serve, &keys) {
+        serve_timer.timer.duration()
+    } else {
+        clock.delta()
+    };
+
+    if serve_timer.timer.tick(delta).just_finished() {
+        for (mut velocity, mut speed) in query.iter_mut() {
+            speed.0 = settings.ball.base_speed;
+            velocity.0 = serve_velocity(serve_timer.receiver, speed.0, gravity.0, &mut rng.0);
+        }
+    }
+}
+
+fn check_winner(
+    mut score: ResMut<Score>,
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    match_config: Res<MatchConfig>,
+    match_timer: Res<MatchTimer>,
+    mut set_score: ResMut<SetScore>,
+    mut state: ResMut<State<GameState>>,
+) {
+    //Practice goes on until the player leaves
+    if !score.is_changed() || mode.scoring() == Scoring::Rally {
+        return;
+    }
+
+    let players = mode.players();
+    let (leader, trailer) = score.top_two(players);
+    let sudden_death = match_timer.sudden_death && leader > trailer;
+    let target = match_config.points_per_set;
+    if !sudden_death && !settings.rules.reaches(target, leader, trailer) {
+        return;
+    }
+
+    //Sudden death only starts with the sets level, so its set decides the match
+    if match_config.has_sets(*mode) {
+        let winner = score.leader(players);
+        set_score.award(winner, &score);
+        let decided = sudden_death && set_score.leader() == Some(winner);
+        if set_score.sets(winner) < match_config.sets_to_win && !decided {
+            info!(
+                "{:?} takes the set, sets {} - {}",
+                winner, set_score.left_sets, set_score.right_sets
+            );
+            *score = Score::default();
+            return;
+        }
+    }
+    state.set(GameState::GameOver).ok();
+}
+
+//The clock stops for pauses along with the rest of the match, and for every serve countdown
+#[allow(clippy::too_many_arguments)]
+fn run_match_clock(
+    clock: Res<SimulationClock>,
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    match_config: Res<MatchConfig>,
+    mut set_score: ResMut<SetScore>,
+    serve_timer: Res<ServeTimer>,
+    mut match_timer: ResMut<MatchTimer>,
+    mut shrink: ResMut<CourtShrink>,
+    mut state: ResMut<State<GameState>>,
+    paddles: Query<(&Paddle, &PaddleSize)>,
+) {
+    if mode.scoring() == Scoring::Rally || !serve_timer.timer.finished() {
+        return;
+    }
+
+    let delta = clock.delta();
+    if !match_timer.sudden_death {
+        let timed_out = match match_timer.clock.as_mut() {
+            Some(timer) => timer.tick(delta).just_finished(),
+            None => return,
+        };
+        if timed_out {
+            //Sets come first, the set being played only breaks a tie on them
+            let players = mode.players();
+            let has_sets = match_config.has_sets(*mode);
+            let (leader, trailer) = score.top_two(players);
+            if has_sets && set_score.leader().is_some() {
+                state.set(GameState::GameOver).ok();
+            } else if leader > trailer {
+                if has_sets {
+                    set_score.award(score.leader(players), &score);
+                }
+                state.set(GameState::GameOver).ok();
+            } else {
+                info!("Time is up with the scores level, sudden death");
+                match_timer.sudden_death = true;
+            }
+        }
+        return;
+    }
+
+    let shrinks = match_timer.shrink.tick(delta).times_finished();
+    if shrinks == 0 {
+        return;
+    }
+    shrink.scale *= (1.0 - MatchTimer::SHRINK).powi(shrinks as i32);
+
+    //Room for the longest paddle and a ball past its end, and along a goal axis enough to
+    //keep a ball's width between the paddles and the middle
+    let diameter = settings.ball.radius * 2.0;
+    let mut min_half_size = Vec2::ZERO;
+    for (paddle, size) in paddles.iter() {
+        let axis = paddle.0.axis();
+        let across = Vec2::ONE - axis;
+        let length = size.0.dot(axis);
+        let thickness = size.0.dot(across);
+        min_half_size = min_half_size
+            .max(axis * (length + diameter) / 2.0)
+            .max(across * (settings.paddle.edge_offset + thickness / 2.0 + diameter));
+    }
+    shrink.min_half_size = min_half_size;
+}
+
+fn score_logger(score: Res<Score>, mode: Res<GameMode>, mut goals: EventReader<GoalEvent>) {
+    if mode.scoring() == Scoring::Rally {
+        return;
+    }
+
+    for goal in goals.iter() {
+        let points: Vec<String> = mode
+            .players()
+            .iter()
+            .map(|player| score.points(*player).to_string())
+            .collect();
+        info!("{:?} scored, {}", goal.scorer, points.join(" - "));
+    }
+}
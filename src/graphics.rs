@@ -0,0 +1,1391 @@
+use crate::gameplay::{GameMode, GoalEvent, InGame, Player, RestartEvent, Score, Scoring};
+use crate::physics::{
+    Ball, CollisionEvent, CourtBounds, Interpolated, Obstacle, Paddle, PaddleHitEvent, PaddleSize,
+    SimulationClock, Velocity,
+};
+use crate::powerups::{PowerUp, PowerUpKind, Shield, SHIELD_WIDTH};
+use crate::replay::Ghost;
+use crate::settings::Settings;
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy::window::WindowMode;
+use bevy_prototype_lyon::entity::ShapeBundle;
+use bevy_prototype_lyon::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+//The window opens with this title, the score is added to it during a match
+pub const WINDOW_TITLE: &str = "Bevy Pong";
+
+//Plugins
+pub struct GraphicsPlugin;
+
+impl Plugin for GraphicsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_plugin(ShapePlugin)
+            .init_resource::<VisualEffects>()
+            .init_resource::<ColorblindMode>()
+            .insert_resource(ScreenShake::default())
+            .add_startup_system(setup_camera.system())
+            .add_system(attach_ball_shapes.system())
+            .add_system(attach_paddle_shapes.system())
+            .add_system(reshape_paddles.system())
+            .add_system(attach_obstacle_shapes.system())
+            .add_system(attach_ghost_shapes.system())
+            .add_system(attach_power_up_shapes.system())
+            .add_system(attach_shield_shapes.system())
+            .add_system(pulse_shields.system())
+            .add_system(trigger_shake.system().label("trigger_shake"))
+            .add_system(apply_shake.system().after("trigger_shake"))
+            .add_system(fullscreen_toggle.system())
+            .add_system(update_window_title.system())
+            .add_system(apply_theme.system())
+            .add_system(color_balls_by_speed.system())
+            .add_system(glow_paddles.system())
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interpolate_transforms
+                    .system()
+                    .after(TransformSystem::TransformPropagate),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(resize_court.system())
+                    .with_system(update_court_score.system().after("update_score"))
+                    .with_system(spawn_particles.system())
+                    .with_system(spawn_goal_particles.system().after("goal_detection"))
+                    .with_system(particle_lifetime.system())
+                    .with_system(spawn_goal_flash.system())
+                    .with_system(goal_flash_fade.system())
+                    .with_system(emit_trail.system())
+                    .with_system(trail_fade.system()),
+            );
+    }
+}
+
+//Resources
+pub struct ScreenShake {
+    pub duration: Timer,
+    pub magnitude: f32,
+}
+
+impl ScreenShake {
+    pub fn new(seconds: f32, magnitude: f32) -> Self {
+        ScreenShake {
+            duration: Timer::from_seconds(seconds, false),
+            magnitude,
+        }
+    }
+
+    //Fades out linearly so the shake settles instead of stopping dead
+    pub fn strength(&self) -> f32 {
+        if self.duration.finished() || self.magnitude <= 0.0 {
+            0.0
+        } else {
+            self.magnitude * (1.0 - self.duration.percent())
+        }
+    }
+}
+
+impl Default for ScreenShake {
+    fn default() -> Self {
+        ScreenShake::new(0.0, 0.0)
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VisualEffects {
+    pub trail: bool,
+    pub particles: bool,
+    pub shake: bool,
+    pub court_score: bool,
+    pub goal_flash: bool,
+    pub rally_colors: bool,
+    pub colorblind: bool,
+}
+
+impl Default for VisualEffects {
+    fn default() -> Self {
+        VisualEffects {
+            trail: true,
+            particles: true,
+            shake: true,
+            court_score: true,
+            goal_flash: true,
+            rally_colors: true,
+            colorblind: false,
+        }
+    }
+}
+
+//Tells the sides apart by shape as well as color: notched paddles on the left, crossed ones on
+//the right, and letters in front of the scores
+#[derive(Default)]
+pub struct ColorblindMode(pub bool);
+
+//Every color the game is drawn in, from a preset or the [colors] section of the settings
+#[derive(Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub background: Color,
+    pub ball: Color,
+    pub left_paddle: Color,
+    pub right_paddle: Color,
+    pub ui_text: Color,
+    pub court_markings: Color,
+}
+
+impl Theme {
+    //Four player matches give the top and bottom paddles the colors of the sides they share
+    pub fn paddle(&self, player: Player) -> Color {
+        match player {
+            Player::Left | Player::Top => self.left_paddle,
+            Player::Right | Player::Bottom => self.right_paddle,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ThemePreset {
+    //The colors written out in the settings file
+    Custom,
+    Classic,
+    Neon,
+    Retro,
+    Pastel,
+}
+
+impl ThemePreset {
+    pub const ALL: [ThemePreset; 5] = [
+        ThemePreset::Custom,
+        ThemePreset::Classic,
+        ThemePreset::Neon,
+        ThemePreset::Retro,
+        ThemePreset::Pastel,
+    ];
+
+    pub fn next(self) -> Self {
+        let index = ThemePreset::ALL.iter().position(|preset| *preset == self);
+        ThemePreset::ALL[index.map_or(0, |index| (index + 1) % ThemePreset::ALL.len())]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ThemePreset::Custom => "CUSTOM",
+            ThemePreset::Classic => "CLASSIC",
+            ThemePreset::Neon => "NEON",
+            ThemePreset::Retro => "RETRO",
+            ThemePreset::Pastel => "PASTEL",
+        }
+    }
+
+    //Custom has no colors of its own
+    pub fn theme(self) -> Option<Theme> {
+        let theme = match self {
+            ThemePreset::Custom => return None,
+            ThemePreset::Classic => Theme {
+                background: Color::rgb(0.1, 0.1, 0.1),
+                ball: Color::rgb(0.9, 0.9, 0.9),
+                left_paddle: Color::rgb(0.9, 0.9, 0.9),
+                right_paddle: Color::rgb(0.9, 0.9, 0.9),
+                ui_text: Color::rgb(0.9, 0.9, 0.9),
+                court_markings: Color::rgb(0.9, 0.9, 0.9),
+            },
+            ThemePreset::Neon => Theme {
+                background: Color::rgb(0.02, 0.0, 0.08),
+                ball: Color::rgb(1.0, 1.0, 0.4),
+                left_paddle: Color::rgb(0.0, 1.0, 0.9),
+                right_paddle: Color::rgb(1.0, 0.2, 0.8),
+                ui_text: Color::rgb(0.9, 0.9, 1.0),
+                court_markings: Color::rgb(0.5, 0.3, 1.0),
+            },
+            ThemePreset::Retro => Theme {
+                background: Color::rgb(0.03, 0.08, 0.03),
+                ball: Color::rgb(0.3, 1.0, 0.4),
+                left_paddle: Color::rgb(0.3, 1.0, 0.4),
+                right_paddle: Color::rgb(0.3, 1.0, 0.4),
+                ui_text: Color::rgb(0.3, 1.0, 0.4),
+                court_markings: Color::rgb(0.15, 0.6, 0.2),
+            },
+            ThemePreset::Pastel => Theme {
+                background: Color::rgb(0.97, 0.94, 0.9),
+                ball: Color::rgb(0.9, 0.55, 0.6),
+                left_paddle: Color::rgb(0.55, 0.7, 0.95),
+                right_paddle: Color::rgb(0.95, 0.7, 0.5),
+                ui_text: Color::rgb(0.35, 0.35, 0.45),
+                court_markings: Color::rgb(0.65, 0.6, 0.75),
+            },
+        };
+        Some(theme)
+    }
+}
+
+impl Default for ThemePreset {
+    fn default() -> Self {
+        ThemePreset::Custom
+    }
+}
+
+//Read before the window opens, the court and menus then follow whatever size it ends up
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: f32,
+    pub height: f32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+}
+
+impl WindowConfig {
+    pub fn descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            title: WINDOW_TITLE.to_string(),
+            width: self.width,
+            height: self.height,
+            vsync: self.vsync,
+            mode: window_mode(self.fullscreen),
+            ..WindowDescriptor::default()
+        }
+    }
+
+    //The court the window will have once it opens, until court_resizer reads the real size
+    pub fn court(&self) -> CourtBounds {
+        CourtBounds {
+            half_width: self.width / 2.0,
+            half_height: self.height / 2.0,
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        //Matches the default court so nothing is scaled until the user asks for it
+        WindowConfig {
+            width: 1280.0,
+            height: 720.0,
+            fullscreen: false,
+            vsync: true,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ShakeConfig {
+    pub duration: f32,
+    pub goal_magnitude: f32,
+    pub hit_magnitude: f32,
+}
+
+impl Default for ShakeConfig {
+    fn default() -> Self {
+        ShakeConfig {
+            duration: 0.3,
+            goal_magnitude: 12.0,
+            hit_magnitude: 3.0,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TrailConfig {
+    pub interval: f32,
+    pub length: f32,
+    pub alpha: f32,
+}
+
+impl Default for TrailConfig {
+    fn default() -> Self {
+        TrailConfig {
+            interval: 0.02,
+            length: 0.2,
+            alpha: 0.4,
+        }
+    }
+}
+
+//Components
+pub struct MainCamera;
+pub struct CourtMarking;
+pub struct CourtScore;
+pub struct Particle;
+pub struct Lifetime(pub Timer);
+
+pub struct GoalFlash {
+    pub initial_alpha: f32,
+}
+
+pub struct TrailEmitter {
+    pub interval: Timer,
+}
+
+pub struct TrailGhost {
+    pub age: Timer,
+    pub initial_alpha: f32,
+}
+
+//Seconds left of a paddle's brighter color after it returns the ball
+pub struct PaddleGlow(pub f32);
+
+//Helpers
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    let amount = amount.clamp(0.0, 1.0);
+    Color::rgba(
+        from.r() + (to.r() - from.r()) * amount,
+        from.g() + (to.g() - from.g()) * amount,
+        from.b() + (to.b() - from.b()) * amount,
+        from.a() + (to.a() - from.a()) * amount,
+    )
+}
+
+//Colors are baked into the mesh when it is built, so recoloring a flat shape rewrites its vertices
+pub fn set_shape_color(
+    colors: &mut ShapeColors,
+    mesh: &Handle<Mesh>,
+    meshes: &mut Assets<Mesh>,
+    color: Color,
+) {
+    colors.main = color;
+    if let Some(mesh) = meshes.get_mut(mesh) {
+        let vertex_colors =
+            vec![[color.r(), color.g(), color.b(), color.a()]; mesh.count_vertices()];
+        mesh.set_attribute(Mesh::ATTRIBUTE_COLOR, vertex_colors);
+    }
+}
+
+//A paddle of the given full size, with its side's cutout when colorblind mode is on
+fn paddle_bundle(
+    player: Player,
+    size: Vec2,
+    color: Color,
+    colorblind: bool,
+    transform: Transform,
+) -> ShapeBundle {
+    let rectangle = shapes::Rectangle {
+        width: size.x,
+        height: size.y,
+        origin: shapes::RectangleOrigin::Center,
+    };
+    if !colorblind {
+        return GeometryBuilder::build_as(
+            &rectangle,
+            ShapeColors::new(color),
+            DrawMode::Fill(FillOptions::default()),
+            transform,
+        );
+    }
+
+    //Laid out along the paddle and across it, with the face toward the middle of the court
+    let axis = player.axis();
+    let face = -player.goal_normal();
+    let half_length = size.dot(axis) / 2.0;
+    let half_thickness = size.dot(face.abs()) / 2.0;
+    let point = |along: f32, across: f32| axis * along + face * across;
+
+    let mut builder = GeometryBuilder::new();
+    let fill_rule = match player {
+        Player::Left | Player::Top => {
+            //Cut halfway into the face across the middle quarter, down to the paddle's center line
+            let notch = half_length / 4.0;
+            builder.add(&shapes::Polygon {
+                points: vec![
+                    point(-half_length, -half_thickness),
+                    point(half_length, -half_thickness),
+                    point(half_length, half_thickness),
+                    point(notch, half_thickness),
+                    point(notch, 0.0),
+                    point(-notch, 0.0),
+                    point(-notch, half_thickness),
+                    point(-half_length, half_thickness),
+                ],
+                closed: true,
+            });
+            FillRule::NonZero
+        }
+        Player::Right | Player::Bottom => {
+            //Filling even-odd leaves the cross as a hole through the solid paddle
+            let (arm, width) = (half_thickness * 0.75, half_thickness * 0.25);
+            builder.add(&rectangle);
+            builder.add(&shapes::Polygon {
+                points: vec![
+                    point(-width, arm),
+                    point(width, arm),
+                    point(width, width),
+                    point(arm, width),
+                    point(arm, -width),
+                    point(width, -width),
+                    point(width, -arm),
+                    point(-width, -arm),
+                    point(-width, -width),
+                    point(-arm, -width),
+                    point(-arm, width),
+                    point(-width, width),
+                ],
+                closed: true,
+            });
+            FillRule::EvenOdd
+        }
+    };
+    builder.build(
+        ShapeColors::new(color),
+        DrawMode::Fill(FillOptions::default().with_fill_rule(fill_rule)),
+        transform,
+    )
+}
+
+fn window_mode(fullscreen: bool) -> WindowMode {
+    if fullscreen {
+        WindowMode::Fullscreen { use_size: false }
+    } else {
+        WindowMode::Windowed
+    }
+}
+
+//Alt+Enter belongs to the window, screens that use Enter on its own check this first
+pub fn alt_held(keys: &Input<KeyCode>) -> bool {
+    keys.pressed(KeyCode::LAlt) || keys.pressed(KeyCode::RAlt)
+}
+
+//Systems
+fn setup_camera(mut commands: Commands) {
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(MainCamera);
+}
+
+fn fullscreen_toggle(keys: Res<Input<KeyCode>>, mut windows: ResMut<Windows>) {
+    if !(alt_held(&keys) && keys.just_pressed(KeyCode::Return)) {
+        return;
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        let fullscreen = window.mode() == WindowMode::Windowed;
+        window.set_mode(window_mode(fullscreen));
+    }
+}
+
+//Readable from the taskbar, so players can keep an eye on a match from another window
+fn update_window_title(
+    score: Res<Score>,
+    mode: Res<GameMode>,
+    state: Res<State<GameState>>,
+    mut windows: ResMut<Windows>,
+) {
+    if !score.is_changed() && !state.is_changed() {
+        return;
+    }
+
+    let title = match state.current() {
+        GameState::Paused => format!("{} — Paused", WINDOW_TITLE),
+        //The final score stays up over the game over screen, practice has no score to show
+        GameState::Playing | GameState::GameOver if mode.scoring() != Scoring::Rally => {
+            let points: Vec<String> = mode
+                .players()
+                .iter()
+                .map(|player| score.points(*player).to_string())
+                .collect();
+            format!("{} — {}", WINDOW_TITLE, points.join(" : "))
+        }
+        _ => WINDOW_TITLE.to_string(),
+    };
+
+    if let Some(window) = windows.get_primary_mut() {
+        if window.title() != title {
+            window.set_title(title);
+        }
+    }
+}
+
+fn resize_court(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    bounds: Res<CourtBounds>,
+    query: Query<Entity, With<CourtMarking>>,
+) {
+    //Drawn on the first frame of a match and again whenever the window resizes the court
+    let drawn = query.iter().next().is_some();
+    if drawn && !bounds.is_changed() && !theme.is_changed() {
+        return;
+    }
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    draw_court(&bounds, theme.court_markings, &mut commands);
+}
+
+fn draw_court(bounds: &CourtBounds, mut color: Color, commands: &mut Commands) {
+    const LINE_WIDTH: f32 = 2.0;
+    const DASH_LENGTH: f32 = 12.0;
+    const DASH_GAP: f32 = 12.0;
+    const CIRCLE_RADIUS: f32 = 60.0;
+    //Behind the trail, ball and paddles
+    const Z: f32 = -1.0;
+
+    color.set_a(0.3);
+
+    //Strokes are centered on the path, so inset the border to keep it fully on screen
+    let border = shapes::Rectangle {
+        width: bounds.half_width * 2.0 - LINE_WIDTH,
+        height: bounds.half_height * 2.0 - LINE_WIDTH,
+        origin: shapes::RectangleOrigin::Center,
+    };
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &border,
+            ShapeColors::new(color),
+            DrawMode::Stroke(StrokeOptions::default().with_line_width(LINE_WIDTH)),
+            Transform::from_xyz(0.0, 0.0, Z),
+        ))
+        .insert(InGame)
+        .insert(CourtMarking);
+
+    let circle = shapes::Circle {
+        radius: CIRCLE_RADIUS,
+        center: Vec2::ZERO,
+    };
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &circle,
+            ShapeColors::new(color),
+            DrawMode::Stroke(StrokeOptions::default().with_line_width(LINE_WIDTH)),
+            Transform::from_xyz(0.0, 0.0, Z),
+        ))
+        .insert(InGame)
+        .insert(CourtMarking);
+
+    let dash = shapes::Rectangle {
+        width: LINE_WIDTH,
+        height: DASH_LENGTH,
+        origin: shapes::RectangleOrigin::Center,
+    };
+    let count = (bounds.half_height * 2.0 / (DASH_LENGTH + DASH_GAP)).ceil() as usize;
+    for index in 0..count {
+        let y = -bounds.half_height
+            + (DASH_GAP + DASH_LENGTH) / 2.0
+            + index as f32 * (DASH_LENGTH + DASH_GAP);
+        commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &dash,
+                ShapeColors::new(color),
+                DrawMode::Fill(FillOptions::default()),
+                Transform::from_xyz(0.0, y, Z),
+            ))
+            .insert(InGame)
+            .insert(CourtMarking);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_court_score(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    colorblind: Res<ColorblindMode>,
+    effects: Res<VisualEffects>,
+    score: Res<Score>,
+    mode: Res<GameMode>,
+    query: Query<Entity, With<CourtScore>>,
+) {
+    const SCORE_X: f32 = 200.0;
+    const SCORE_Y: f32 = 200.0;
+
+    //Paths are only tessellated when added, so new digits mean new entities
+    let drawn = query.iter().next().is_some();
+    let practice = *mode == GameMode::Practice;
+    if !effects.court_score
+        || practice
+        || (drawn && !score.is_changed() && !theme.is_changed() && !colorblind.is_changed())
+    {
+        return;
+    }
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let mut color = theme.court_markings;
+    color.set_a(0.5);
+    for &player in mode.players() {
+        //Two players share the top of the court, with four each gets a spot on their own side
+        let position = match (*mode, player) {
+            (GameMode::FourPlayer, _) => player.goal_normal() * Vec2::new(SCORE_X, SCORE_Y) / 2.0,
+            (_, Player::Left) => Vec2::new(-SCORE_X, SCORE_Y),
+            _ => Vec2::new(SCORE_X, SCORE_Y),
+        };
+        let points = score.points(player);
+        let label = if colorblind.0 {
+            format!("{}:{}", player.initial(), points)
+        } else {
+            points.to_string()
+        };
+        commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &seven_segment(&label).build(),
+                ShapeColors::new(color),
+                DrawMode::Fill(FillOptions::default()),
+                Transform::from_translation(position.extend(-1.0)),
+            ))
+            .insert(InGame)
+            .insert(CourtScore);
+    }
+}
+
+//Outlines digits, the player initials and colons in 7-segment style, centered on the origin
+fn seven_segment(text: &str) -> PathBuilder {
+    const WIDTH: f32 = 50.0;
+    const HEIGHT: f32 = 100.0;
+    const THICKNESS: f32 = 10.0;
+    const SPACING: f32 = 20.0;
+    //Segments a to g as bits, a top, then clockwise, g in the middle
+    const DIGITS: [u8; 10] = [
+        0b0111111, 0b0000110, 0b1011011, 0b1001111, 0b1100110, 0b1101101, 0b1111101, 0b0000111,
+        0b1111111, 0b1101111,
+    ];
+
+    //Segments butt against each other instead of overlapping, overlaps would cancel out when filled
+    let (mid_low, mid_high) = (
+        HEIGHT / 2.0 - THICKNESS / 2.0,
+        HEIGHT / 2.0 + THICKNESS / 2.0,
+    );
+    let segments = [
+        (Vec2::new(0.0, HEIGHT - THICKNESS), Vec2::new(WIDTH, HEIGHT)),
+        (
+            Vec2::new(WIDTH - THICKNESS, mid_high),
+            Vec2::new(WIDTH, HEIGHT - THICKNESS),
+        ),
+        (
+            Vec2::new(WIDTH - THICKNESS, THICKNESS),
+            Vec2::new(WIDTH, mid_low),
+        ),
+        (Vec2::new(0.0, 0.0), Vec2::new(WIDTH, THICKNESS)),
+        (Vec2::new(0.0, THICKNESS), Vec2::new(THICKNESS, mid_low)),
+        (
+            Vec2::new(0.0, mid_high),
+            Vec2::new(THICKNESS, HEIGHT - THICKNESS),
+        ),
+        (Vec2::new(0.0, mid_low), Vec2::new(WIDTH, mid_high)),
+    ];
+    //A colon is just two dots, a third of the way up and down
+    let dots = [
+        (
+            Vec2::new(0.0, HEIGHT / 3.0 - THICKNESS / 2.0),
+            Vec2::new(THICKNESS, HEIGHT / 3.0 + THICKNESS / 2.0),
+        ),
+        (
+            Vec2::new(0.0, HEIGHT * 2.0 / 3.0 - THICKNESS / 2.0),
+            Vec2::new(THICKNESS, HEIGHT * 2.0 / 3.0 + THICKNESS / 2.0),
+        ),
+    ];
+
+    //Each glyph's boxes and how wide it is, letters are the lowercase shapes a 7-segment can show
+    let glyphs: Vec<(Vec<(Vec2, Vec2)>, f32)> = text
+        .chars()
+        .filter_map(|glyph| {
+            let bits = match glyph {
+                '0'..='9' => DIGITS[glyph as usize - '0' as usize],
+                'L' => 0b0111000,
+                'R' => 0b1010000,
+                'T' => 0b1111000,
+                'B' => 0b1111100,
+                ':' => return Some((dots.to_vec(), THICKNESS)),
+                _ => return None,
+            };
+            let lit = segments
+                .iter()
+                .enumerate()
+                .filter(|(bit, _)| bits & (1 << bit) != 0)
+                .map(|(_, segment)| *segment)
+                .collect();
+            Some((lit, WIDTH))
+        })
+        .collect();
+    let total_width: f32 = glyphs.iter().map(|(_, width)| width + SPACING).sum::<f32>() - SPACING;
+    let mut offset = Vec2::new(-total_width / 2.0, -HEIGHT / 2.0);
+
+    let mut builder = PathBuilder::new();
+    for (boxes, width) in glyphs {
+        for (min, max) in boxes {
+            let (min, max) = (offset + min, offset + max);
+            builder.move_to(min);
+            builder.line_to(Vec2::new(max.x, min.y));
+            builder.line_to(max);
+            builder.line_to(Vec2::new(min.x, max.y));
+            builder.close();
+        }
+        offset.x += width + SPACING;
+    }
+    builder
+}
+
+fn attach_ball_shapes(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    theme: Res<Theme>,
+    query: Query<(Entity, &Transform), Added<Ball>>,
+) {
+    let shape = shapes::Circle {
+        radius: settings.ball.radius,
+        center: Vec2::ZERO,
+    };
+
+    for (entity, transform) in query.iter() {
+        commands
+            .entity(entity)
+            .insert_bundle(GeometryBuilder::build_as(
+                &shape,
+                ShapeColors::new(theme.ball),
+                DrawMode::Fill(FillOptions::default()),
+                *transform,
+            ))
+            .insert(TrailEmitter {
+                interval: Timer::from_seconds(settings.trail.interval, true),
+            })
+            .insert(Interpolated {
+                previous: transform.translation,
+            });
+    }
+}
+
+fn attach_paddle_shapes(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    colorblind: Res<ColorblindMode>,
+    query: Query<(Entity, &Paddle, &PaddleSize, &Transform), Added<Paddle>>,
+) {
+    for (entity, paddle, size, transform) in query.iter() {
+        let color = theme.paddle(paddle.0);
+        commands
+            .entity(entity)
+            .insert_bundle(paddle_bundle(
+                paddle.0,
+                size.0,
+                color,
+                colorblind.0,
+                *transform,
+            ))
+            .insert(Interpolated {
+                previous: transform.translation,
+            })
+            .insert(PaddleGlow(0.0));
+    }
+}
+
+fn attach_obstacle_shapes(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    query: Query<(Entity, &Transform, &Obstacle), Added<Obstacle>>,
+) {
+    for (entity, transform, obstacle) in query.iter() {
+        let shape = shapes::Rectangle {
+            width: obstacle.size.x,
+            height: obstacle.size.y,
+            origin: shapes::RectangleOrigin::Center,
+        };
+
+        commands
+            .entity(entity)
+            .insert_bundle(GeometryBuilder::build_as(
+                &shape,
+                ShapeColors::new(theme.court_markings),
+                DrawMode::Fill(FillOptions::default()),
+                *transform,
+            ))
+            .insert(Interpolated {
+                previous: transform.translation,
+            });
+    }
+}
+
+fn attach_ghost_shapes(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    theme: Res<Theme>,
+    query: Query<(Entity, &Transform, &Ghost), Added<Ghost>>,
+) {
+    let mut color = theme.ball;
+    color.set_a(0.25);
+
+    for (entity, transform, ghost) in query.iter() {
+        let bundle = match ghost {
+            Ghost::Ball(_) => GeometryBuilder::build_as(
+                &shapes::Circle {
+                    radius: settings.ball.radius,
+                    center: Vec2::ZERO,
+                },
+                ShapeColors::new(color),
+                DrawMode::Fill(FillOptions::default()),
+                *transform,
+            ),
+            Ghost::Paddle(_, size) => GeometryBuilder::build_as(
+                &shapes::Rectangle {
+                    width: size.x,
+                    height: size.y,
+                    origin: shapes::RectangleOrigin::Center,
+                },
+                ShapeColors::new(color),
+                DrawMode::Fill(FillOptions::default()),
+                *transform,
+            ),
+        };
+        commands.entity(entity).insert_bundle(bundle);
+    }
+}
+
+fn attach_power_up_shapes(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    query: Query<(Entity, &Transform, &PowerUp), Added<PowerUp>>,
+) {
+    let size = settings.powerups.size;
+    let shape = shapes::Polygon {
+        points: vec![
+            Vec2::new(0.0, size),
+            Vec2::new(size, 0.0),
+            Vec2::new(0.0, -size),
+            Vec2::new(-size, 0.0),
+        ],
+        closed: true,
+    };
+
+    for (entity, transform, power_up) in query.iter() {
+        //Each kind gets its own color, with a soft halo around it
+        let color = power_up_color(power_up.0);
+        let mut glow = color;
+        glow.set_a(0.4);
+
+        commands
+            .entity(entity)
+            .insert_bundle(GeometryBuilder::build_as(
+                &shape,
+                ShapeColors::outlined(color, glow),
+                DrawMode::Outlined {
+                    fill_options: FillOptions::default(),
+                    outline_options: StrokeOptions::default().with_line_width(6.0),
+                },
+                *transform,
+            ));
+    }
+}
+
+fn power_up_color(kind: PowerUpKind) -> Color {
+    match kind {
+        PowerUpKind::BigPaddle => Color::rgb(0.3, 0.9, 0.4),
+        PowerUpKind::NarrowOpponentPaddle => Color::rgb(0.9, 0.3, 0.3),
+        PowerUpKind::SlowBall => Color::rgb(0.3, 0.6, 1.0),
+        PowerUpKind::FastBall => Color::rgb(1.0, 0.7, 0.2),
+        PowerUpKind::Shield => Color::rgb(0.7, 0.4, 1.0),
+        PowerUpKind::StickyPaddle => Color::rgb(1.0, 0.4, 0.8),
+    }
+}
+
+//Built at unit height and stretched to the court, so resizing never needs a new mesh
+fn attach_shield_shapes(
+    mut commands: Commands,
+    bounds: Res<CourtBounds>,
+    query: Query<(Entity, &Shield, &Transform), Added<Shield>>,
+) {
+    let shape = shapes::Rectangle {
+        width: SHIELD_WIDTH,
+        height: 1.0,
+        origin: shapes::RectangleOrigin::Center,
+    };
+
+    for (entity, shield, transform) in query.iter() {
+        let mut transform = *transform;
+        transform.scale.y = bounds.half_size().dot(shield.owner.axis()) * 2.0;
+        //Shields across the top or bottom goal lie on their side
+        if shield.owner.axis() == Vec2::X {
+            transform.rotation = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+        }
+        commands
+            .entity(entity)
+            .insert_bundle(GeometryBuilder::build_as(
+                &shape,
+                ShapeColors::new(power_up_color(PowerUpKind::Shield)),
+                DrawMode::Fill(FillOptions::default()),
+                transform,
+            ));
+    }
+}
+
+//Steady while fresh, then flickers faster and faster as it runs out
+fn pulse_shields(
+    bounds: Res<CourtBounds>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut query: Query<(&Shield, &mut Transform, &mut ShapeColors, &Handle<Mesh>)>,
+) {
+    const WARNING_SECONDS: f32 = 2.0;
+
+    for (shield, mut transform, mut colors, mesh) in query.iter_mut() {
+        transform.scale.y = bounds.half_size().dot(shield.owner.axis()) * 2.0;
+
+        let remaining = shield.timer.duration().as_secs_f32() - shield.timer.elapsed_secs();
+        let alpha = if remaining > WARNING_SECONDS {
+            0.8
+        } else {
+            let rate = 2.0 + 4.0 * (1.0 - remaining / WARNING_SECONDS);
+            0.5 + 0.3 * (remaining * rate * std::f32::consts::TAU).cos()
+        };
+        set_alpha(&mut colors, mesh, &mut meshes, alpha);
+    }
+}
+
+//Paths are only tessellated when added, so a resized paddle or a switch of colorblind mode swaps
+//in a whole new shape
+#[allow(clippy::type_complexity)]
+fn reshape_paddles(
+    mut commands: Commands,
+    colorblind: Res<ColorblindMode>,
+    query: Query<(
+        Entity,
+        &Paddle,
+        &PaddleSize,
+        ChangeTrackers<PaddleSize>,
+        &Transform,
+        &ShapeColors,
+    )>,
+) {
+    let restyled = colorblind.is_changed() && !colorblind.is_added();
+
+    for (entity, paddle, size, size_tracker, transform, colors) in query.iter() {
+        //Shapes attached this frame were already built at the new size
+        if !restyled && (!size_tracker.is_changed() || size_tracker.is_added()) {
+            continue;
+        }
+        commands
+            .entity(entity)
+            .remove_bundle::<ShapeBundle>()
+            .insert_bundle(paddle_bundle(
+                paddle.0,
+                size.0,
+                colors.main,
+                colorblind.0,
+                *transform,
+            ));
+    }
+}
+
+//Balls and paddles keep themselves up to date, everything else drawn once is recolored here
+fn apply_theme(
+    theme: Res<Theme>,
+    mut clear_color: ResMut<ClearColor>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut obstacles: Query<(&mut ShapeColors, &Handle<Mesh>), With<Obstacle>>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    clear_color.0 = theme.background;
+    for (mut colors, mesh) in obstacles.iter_mut() {
+        set_shape_color(&mut colors, mesh, &mut meshes, theme.court_markings);
+    }
+}
+
+//Heats up from the theme's ball color toward red as the ball closes in on its top speed
+fn color_balls_by_speed(
+    settings: Res<Settings>,
+    theme: Res<Theme>,
+    effects: Res<VisualEffects>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut query: Query<(&Velocity, &mut ShapeColors, &Handle<Mesh>), With<Ball>>,
+) {
+    let base = theme.ball;
+    let (slowest, fastest) = (settings.ball.base_speed, settings.ball.max_speed);
+    for (velocity, mut colors, mesh) in query.iter_mut() {
+        let heat = if effects.rally_colors {
+            (velocity.0.length() - slowest) / (fastest - slowest).max(f32::EPSILON)
+        } else {
+            0.0
+        };
+        let color = mix(base, Color::RED, heat);
+        if colors.main != color {
+            set_shape_color(&mut colors, mesh, &mut meshes, color);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn glow_paddles(
+    time: Res<Time>,
+    theme: Res<Theme>,
+    effects: Res<VisualEffects>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut hits: EventReader<PaddleHitEvent>,
+    mut goals: EventReader<GoalEvent>,
+    mut restarts: EventReader<RestartEvent>,
+    mut query: Query<(&Paddle, &mut PaddleGlow, &mut ShapeColors, &Handle<Mesh>)>,
+) {
+    const GLOW_SECONDS: f32 = 0.15;
+    const GLOW_AMOUNT: f32 = 0.6;
+
+    for hit in hits.iter() {
+        for (paddle, mut glow, _, _) in query.iter_mut() {
+            if paddle.0 == hit.player {
+                glow.0 = GLOW_SECONDS;
+            }
+        }
+    }
+
+    //A new point starts with every paddle back to its plain color
+    let reset = goals.iter().count() + restarts.iter().count() > 0;
+    for (paddle, mut glow, mut colors, mesh) in query.iter_mut() {
+        if reset || !effects.rally_colors {
+            glow.0 = 0.0;
+        }
+
+        let color = mix(
+            theme.paddle(paddle.0),
+            Color::WHITE,
+            GLOW_AMOUNT * glow.0 / GLOW_SECONDS,
+        );
+        if colors.main != color {
+            set_shape_color(&mut colors, mesh, &mut meshes, color);
+        }
+        glow.0 = (glow.0 - time.delta_seconds()).max(0.0);
+    }
+}
+
+fn trigger_shake(
+    settings: Res<Settings>,
+    effects: Res<VisualEffects>,
+    mut shake: ResMut<ScreenShake>,
+    mut goals: EventReader<GoalEvent>,
+    mut collisions: EventReader<CollisionEvent>,
+    paddles: Query<(), With<Paddle>>,
+) {
+    if !effects.shake {
+        return;
+    }
+
+    let config = &settings.shake;
+    //Goals restart the shake rather than adding to it, so close multiball goals can't pile up
+    if goals.iter().next().is_some() {
+        *shake = ScreenShake::new(config.duration, config.goal_magnitude);
+    }
+
+    //A paddle hit shouldn't cut a stronger goal shake short
+    let paddle_hit = collisions
+        .iter()
+        .any(|collision| collision.is_impact() && paddles.get(collision.entity_b).is_ok());
+    if paddle_hit && shake.strength() < config.hit_magnitude {
+        *shake = ScreenShake::new(config.duration, config.hit_magnitude);
+    }
+}
+
+fn apply_shake(
+    time: Res<Time>,
+    mut shake: ResMut<ScreenShake>,
+    mut query: Query<&mut Transform, With<MainCamera>>,
+) {
+    shake.duration.tick(time.delta());
+    let strength = shake.strength();
+
+    let offset = if strength > 0.0 {
+        let mut rng = rand::thread_rng();
+        Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0)) * strength
+    } else {
+        Vec2::ZERO
+    };
+
+    for mut transform in query.iter_mut() {
+        //The camera rests at the origin, only its depth is kept
+        transform.translation.x = offset.x;
+        transform.translation.y = offset.y;
+    }
+}
+
+fn interpolate_transforms(
+    clock: Res<SimulationClock>,
+    state: Res<State<GameState>>,
+    mut query: Query<(&Transform, &Interpolated, &mut GlobalTransform)>,
+) {
+    //Physics only steps while playing, otherwise show exactly where things are
+    let alpha = if *state.current() == GameState::Playing {
+        clock.overstep().min(1.0)
+    } else {
+        1.0
+    };
+
+    //Root transforms are only propagated when they change, so this has to run every frame
+    for (transform, interpolated, mut global) in query.iter_mut() {
+        global.translation = interpolated.previous.lerp(transform.translation, alpha);
+    }
+}
+
+fn spawn_particles(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    settings: Res<Settings>,
+    effects: Res<VisualEffects>,
+    mut collisions: EventReader<CollisionEvent>,
+    paddles: Query<&Paddle>,
+    particles: Query<(), With<Particle>>,
+) {
+    if !effects.particles {
+        return;
+    }
+
+    const LIFETIME: f32 = 0.5;
+    //Fast rallies and multi-ball hit often, so bursts stop once this many are alive
+    const MAX_PARTICLES: usize = 120;
+
+    let shape = shapes::Circle {
+        radius: 2.0,
+        center: Vec2::ZERO,
+    };
+    let mut rng = rand::thread_rng();
+    let mut live = particles.iter().count();
+
+    for collision in collisions.iter().filter(|collision| collision.is_impact()) {
+        let player = match paddles.get(collision.entity_b) {
+            Ok(paddle) => paddle.0,
+            Err(_) => continue,
+        };
+        //The burst starts on the paddle face rather than at the ball's center
+        let position = collision.position - collision.normal * settings.ball.radius;
+        //Spray back out into the court, away from the paddle face
+        let outward = -player.goal_normal();
+        let axis = player.axis();
+
+        let count = rng
+            .gen_range(8..=15)
+            .min(MAX_PARTICLES.saturating_sub(live));
+        live += count;
+        for _ in 0..count {
+            let angle = rng.gen_range(-80.0_f32..=80.0).to_radians();
+            let speed = rng.gen_range(60.0..=180.0);
+            let velocity = (outward * angle.cos() + axis * angle.sin()) * speed;
+
+            commands
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &shape,
+                    ShapeColors::new(theme.paddle(player)),
+                    DrawMode::Fill(FillOptions::default()),
+                    Transform::from_translation(position.extend(0.5)),
+                ))
+                .insert(InGame)
+                .insert(Particle)
+                .insert(Velocity(velocity))
+                .insert(Lifetime(Timer::from_seconds(LIFETIME, false)));
+        }
+    }
+}
+
+//A burst where the ball left the court, thrown back the way it came and harder for faster goals
+fn spawn_goal_particles(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    bounds: Res<CourtBounds>,
+    effects: Res<VisualEffects>,
+    mut goals: EventReader<GoalEvent>,
+) {
+    const LIFETIME: f32 = 0.8;
+    const COUNT: usize = 24;
+
+    if !effects.particles {
+        return;
+    }
+
+    let shape = shapes::Circle {
+        radius: 3.0,
+        center: Vec2::ZERO,
+    };
+    let mut rng = rand::thread_rng();
+
+    for goal in goals.iter() {
+        let half = bounds.half_size();
+        let exit = Vec2::new(goal.ball_exit_x, goal.ball_exit_y).clamp(-half, half);
+        let speed = goal.ball_exit_velocity.length();
+        //A ball that somehow stopped out of play still sprays straight back from the goal
+        let back = if speed > 0.0 {
+            -goal.ball_exit_velocity / speed
+        } else {
+            -goal.conceder.goal_normal()
+        };
+
+        for _ in 0..COUNT {
+            let angle = rng.gen_range(-60.0_f32..=60.0).to_radians();
+            let direction = Vec2::new(
+                back.x * angle.cos() - back.y * angle.sin(),
+                back.x * angle.sin() + back.y * angle.cos(),
+            );
+            let velocity = direction * speed * rng.gen_range(0.2..=0.6);
+
+            commands
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &shape,
+                    ShapeColors::new(theme.ball),
+                    DrawMode::Fill(FillOptions::default()),
+                    Transform::from_translation(exit.extend(0.5)),
+                ))
+                .insert(InGame)
+                .insert(Particle)
+                .insert(Velocity(velocity))
+                .insert(Lifetime(Timer::from_seconds(LIFETIME, false)));
+        }
+    }
+}
+
+fn particle_lifetime(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut query: Query<(Entity, &mut Lifetime, &mut ShapeColors, &Handle<Mesh>), With<Particle>>,
+) {
+    for (entity, mut lifetime, mut colors, mesh) in query.iter_mut() {
+        if lifetime.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        set_alpha(&mut colors, mesh, &mut meshes, 1.0 - lifetime.0.percent());
+    }
+}
+
+//Tints the conceding player's half of the court for a moment
+fn spawn_goal_flash(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    bounds: Res<CourtBounds>,
+    effects: Res<VisualEffects>,
+    mut goals: EventReader<GoalEvent>,
+) {
+    const LIFETIME: f32 = 0.4;
+    const ALPHA: f32 = 0.25;
+
+    if !effects.goal_flash {
+        return;
+    }
+
+    for goal in goals.iter() {
+        let normal = goal.conceder.goal_normal();
+        let half = bounds.half_size();
+        //Full size along the goal line, half the court deep
+        let size = half * 2.0 - normal.abs() * half;
+        let shape = shapes::Rectangle {
+            width: size.x,
+            height: size.y,
+            origin: shapes::RectangleOrigin::Center,
+        };
+        let center = normal * half / 2.0;
+
+        let mut color = theme.court_markings;
+        color.set_a(ALPHA);
+        commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shape,
+                ShapeColors::new(color),
+                DrawMode::Fill(FillOptions::default()),
+                Transform::from_translation(center.extend(0.25)),
+            ))
+            .insert(InGame)
+            .insert(GoalFlash {
+                initial_alpha: ALPHA,
+            })
+            .insert(Lifetime(Timer::from_seconds(LIFETIME, false)));
+    }
+}
+
+fn goal_flash_fade(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut query: Query<(
+        Entity,
+        &GoalFlash,
+        &mut Lifetime,
+        &mut ShapeColors,
+        &Handle<Mesh>,
+    )>,
+) {
+    for (entity, flash, mut lifetime, mut colors, mesh) in query.iter_mut() {
+        if lifetime.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let alpha = flash.initial_alpha * (1.0 - lifetime.0.percent());
+        set_alpha(&mut colors, mesh, &mut meshes, alpha);
+    }
+}
+
+fn emit_trail(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    theme: Res<Theme>,
+    effects: Res<VisualEffects>,
+    mut query: Query<(&Transform, &Velocity, &mut TrailEmitter), With<Ball>>,
+) {
+    if !effects.trail {
+        return;
+    }
+
+    let shape = shapes::Circle {
+        radius: settings.ball.radius,
+        center: Vec2::ZERO,
+    };
+    let mut color = theme.ball;
+    color.set_a(settings.trail.alpha);
+
+    for (transform, velocity, mut emitter) in query.iter_mut() {
+        emitter.interval.tick(time.delta());
+
+        //A ball waiting to be served would just pile ghosts up on itself
+        if velocity.0 == Vec2::ZERO || !emitter.interval.just_finished() {
+            continue;
+        }
+
+        commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shape,
+                ShapeColors::new(color),
+                DrawMode::Fill(FillOptions::default()),
+                Transform::from_translation(transform.translation.truncate().extend(-0.1)),
+            ))
+            .insert(InGame)
+            .insert(TrailGhost {
+                age: Timer::from_seconds(settings.trail.length, false),
+                initial_alpha: settings.trail.alpha,
+            });
+    }
+}
+
+fn trail_fade(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut query: Query<(
+        Entity,
+        &mut TrailGhost,
+        &mut Transform,
+        &mut ShapeColors,
+        &Handle<Mesh>,
+    )>,
+) {
+    //Ghosts always run out their timer, so even long rallies keep a bounded trail
+    for (entity, mut ghost, mut transform, mut colors, mesh) in query.iter_mut() {
+        if ghost.age.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let remaining = 1.0 - ghost.age.percent();
+        transform.scale = Vec3::splat(remaining);
+        set_alpha(
+            &mut colors,
+            mesh,
+            &mut meshes,
+            ghost.initial_alpha * remaining,
+        );
+    }
+}
+
+fn set_alpha(colors: &mut ShapeColors, mesh: &Handle<Mesh>, meshes: &mut Assets<Mesh>, alpha: f32) {
+    let mut color = colors.main;
+    color.set_a(alpha);
+    set_shape_color(colors, mesh, meshes, color);
+}
@@ -0,0 +1,291 @@
+use crate::gameplay::{GameMode, GoalEvent, MatchStats, Player, RestartEvent, Score, ServeTimer};
+use crate::physics::{PaddleHitEvent, SimulationClock, TimeScale};
+use crate::recording::Playback;
+use crate::settings::Settings;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+//Plugins
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(MatchHistory::load(&MatchHistory::path()))
+            .insert_resource(HighScore::load(&HighScore::path()))
+            .insert_resource(SessionStats::default())
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(
+                    track_session_stats
+                        .system()
+                        .after("goal_detection")
+                        .after("request_restart"),
+                ),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(record_match.system())
+                    .with_system(record_high_score.system()),
+            );
+    }
+}
+
+//Resources
+//Every finished match, oldest first
+#[derive(Default, Deserialize, Serialize)]
+pub struct MatchHistory {
+    pub matches: Vec<MatchRecord>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct MatchRecord {
+    pub date: String,
+    //Points in the order of the mode's players
+    pub score: Vec<u32>,
+    pub points_to_win: u32,
+    pub longest_rally: u32,
+}
+
+//Bests across every match since the game started, kept for the high scores
+#[derive(Default)]
+pub struct SessionStats {
+    //Most paddle hits between two goals
+    pub max_rally: u32,
+    //Goals by each player, in the order of Player::ALL so four player matches count the top and
+    //bottom paddles too
+    pub goals_scored: [u32; 4],
+    //Whole seconds of the longest point
+    pub longest_volley: u32,
+    rally: u32,
+    volley: f32,
+}
+
+//The best anyone has done on this machine
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HighScore {
+    pub longest_rally: u32,
+    //Most points by one player in a match
+    pub highest_score: u32,
+    //Most goals by one side in a session
+    pub most_goals: u32,
+    pub longest_volley: u32,
+}
+
+impl MatchHistory {
+    const FILE_NAME: &'static str = "history.json";
+
+    pub fn path() -> PathBuf {
+        //Falls back to the working directory like the settings do
+        data_dir()
+            .map(|dir| dir.join(MatchHistory::FILE_NAME))
+            .unwrap_or_else(|| PathBuf::from(MatchHistory::FILE_NAME))
+    }
+
+    pub fn load(path: &Path) -> Self {
+        //Losing the history is better than refusing to start
+        match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|error| {
+                warn!("Failed to parse {}: {}", path.display(), error);
+                MatchHistory::default()
+            }),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => MatchHistory::default(),
+            Err(error) => {
+                warn!("Failed to read {}: {}", path.display(), error);
+                MatchHistory::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) {
+        //Written next to the old file and swapped in, so a crash mid-write keeps the old records
+        let temp = path.with_extension("json.tmp");
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+                }
+                std::fs::write(&temp, contents).map_err(|error| error.to_string())?;
+                std::fs::rename(&temp, path).map_err(|error| error.to_string())
+            });
+
+        if let Err(error) = result {
+            warn!("Failed to write {}: {}", path.display(), error);
+        }
+    }
+}
+
+impl HighScore {
+    const FILE_NAME: &'static str = "save/highscore.toml";
+
+    pub fn path() -> PathBuf {
+        data_dir()
+            .map(|dir| dir.join(HighScore::FILE_NAME))
+            .unwrap_or_else(|| PathBuf::from(HighScore::FILE_NAME))
+    }
+
+    pub fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|error| {
+                warn!("Failed to parse {}: {}", path.display(), error);
+                HighScore::default()
+            }),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                let high_score = HighScore::default();
+                high_score.save(path);
+                high_score
+            }
+            Err(error) => {
+                warn!("Failed to read {}: {}", path.display(), error);
+                HighScore::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) {
+        let result = toml::to_string_pretty(self)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+                }
+                std::fs::write(path, contents).map_err(|error| error.to_string())
+            });
+
+        if let Err(error) = result {
+            warn!("Failed to write {}: {}", path.display(), error);
+        }
+    }
+
+    //Raises every record this beats, true if any of them changed
+    fn beat(&mut self, other: &HighScore) -> bool {
+        let mut beaten = false;
+        for (record, value) in [
+            (&mut self.longest_rally, other.longest_rally),
+            (&mut self.highest_score, other.highest_score),
+            (&mut self.most_goals, other.most_goals),
+            (&mut self.longest_volley, other.longest_volley),
+        ] {
+            if value > *record {
+                *record = value;
+                beaten = true;
+            }
+        }
+        beaten
+    }
+}
+
+//Helpers
+pub(crate) fn data_dir() -> Option<PathBuf> {
+    //An empty variable counts as unset rather than the working directory
+    let env_dir = |name| {
+        std::env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+    let base = if cfg!(windows) {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        env_dir("XDG_DATA_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".local/share")))
+    };
+    base.map(|dir| dir.join("bevy-pong"))
+}
+
+//Systems
+fn record_match(
+    settings: Res<Settings>,
+    score: Res<Score>,
+    mode: Res<GameMode>,
+    stats: Res<MatchStats>,
+    mut history: ResMut<MatchHistory>,
+    playback: Option<Res<Playback>>,
+) {
+    //Watching a match again doesn't count as playing it
+    if playback.is_some() {
+        return;
+    }
+
+    history.matches.push(MatchRecord {
+        date: chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+        score: mode
+            .players()
+            .iter()
+            .map(|player| score.points(*player))
+            .collect(),
+        points_to_win: settings.rules.points_to_win,
+        longest_rally: stats.longest_rally,
+    });
+    history.save(&MatchHistory::path());
+}
+
+#[allow(clippy::too_many_arguments)]
+fn track_session_stats(
+    clock: Res<SimulationClock>,
+    time_scale: Res<TimeScale>,
+    serve_timer: Res<ServeTimer>,
+    mut paddle_hits: EventReader<PaddleHitEvent>,
+    mut goals: EventReader<GoalEvent>,
+    mut restarts: EventReader<RestartEvent>,
+    mut stats: ResMut<SessionStats>,
+    playback: Option<Res<Playback>>,
+) {
+    if playback.is_some() {
+        return;
+    }
+
+    for _ in paddle_hits.iter() {
+        stats.rally += 1;
+        stats.max_rally = stats.max_rally.max(stats.rally);
+    }
+
+    //A point lasts from the serve to the goal, in game time so slow motion doesn't pad it
+    if serve_timer.timer.finished() {
+        stats.volley += clock.delta_seconds() * time_scale.0;
+        stats.longest_volley = stats.longest_volley.max(stats.volley as u32);
+    }
+
+    for goal in goals.iter() {
+        stats.rally = 0;
+        stats.volley = 0.0;
+        if let Some(index) = Player::ALL.iter().position(|player| *player == goal.scorer) {
+            stats.goals_scored[index] += 1;
+        }
+    }
+
+    //A restarted point starts over, the hits and time before the restart don't carry into it
+    if restarts.iter().count() > 0 {
+        stats.rally = 0;
+        stats.volley = 0.0;
+    }
+}
+
+fn record_high_score(
+    score: Res<Score>,
+    mode: Res<GameMode>,
+    stats: Res<SessionStats>,
+    mut high_score: ResMut<HighScore>,
+    playback: Option<Res<Playback>>,
+) {
+    if playback.is_some() {
+        return;
+    }
+
+    let session = HighScore {
+        longest_rally: stats.max_rally,
+        highest_score: mode
+            .players()
+            .iter()
+            .map(|player| score.points(*player))
+            .max()
+            .unwrap_or(0),
+        most_goals: stats.goals_scored.iter().copied().max().unwrap_or(0),
+        longest_volley: stats.longest_volley,
+    };
+    if high_score.beat(&session) {
+        high_score.save(&HighScore::path());
+    }
+}
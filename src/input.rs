@@ -0,0 +1,595 @@
+use crate::ai::AiControlled;
+use crate::gameplay::{GameMode, Player};
+use crate::graphics::MainCamera;
+use crate::physics::{Paddle, PaddleAccel, SimulationClock, Velocity};
+use crate::recording::Playback;
+use crate::settings::Settings;
+use crate::GameState;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+//Plugins
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<KeyBindings>()
+            .insert_resource(Inputs::default())
+            .insert_resource(ConnectedGamepads::default())
+            .insert_resource(MouseTarget::default())
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                gamepad_connections.system().after(InputSystem),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                pause_system
+                    .system()
+                    .label("pause_system")
+                    .after(InputSystem),
+            );
+
+        //A replay feeds the recorded inputs over whatever the keyboard, gamepads and mouse said
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(input_decoder.system().label("input_decoder"))
+                .with_system(track_cursor.system().label("track_cursor"))
+                .with_system(
+                    mouse_decoder
+                        .system()
+                        .label("mouse_decoder")
+                        .after("input_decoder")
+                        .after("track_cursor"),
+                )
+                .with_system(
+                    playback_decoder
+                        .system()
+                        .label("playback_decoder")
+                        .after("mouse_decoder"),
+                )
+                .with_system(
+                    handle_inputs
+                        .system()
+                        .label("handle_inputs")
+                        .after("playback_decoder"),
+                ),
+        );
+    }
+}
+
+//Resources
+//Positive axis is up for the side paddles and right for the top and bottom ones
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct PaddleInputs {
+    pub axis: f32,
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct Inputs {
+    pub left: PaddleInputs,
+    pub right: PaddleInputs,
+    pub top: PaddleInputs,
+    pub bottom: PaddleInputs,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Action {
+    LeftUp,
+    LeftDown,
+    RightUp,
+    RightDown,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    LeftServe,
+    RightServe,
+    TopServe,
+    BottomServe,
+    Pause,
+    Serve,
+    SlowMotion,
+    FastForward,
+    Restart,
+    Replay,
+    Mute,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub left_up: KeyCode,
+    pub left_down: KeyCode,
+    pub right_up: KeyCode,
+    pub right_down: KeyCode,
+    pub top_left: KeyCode,
+    pub top_right: KeyCode,
+    pub bottom_left: KeyCode,
+    pub bottom_right: KeyCode,
+    pub left_serve: KeyCode,
+    pub right_serve: KeyCode,
+    pub top_serve: KeyCode,
+    pub bottom_serve: KeyCode,
+    pub pause: KeyCode,
+    pub serve: KeyCode,
+    pub slow_motion: KeyCode,
+    pub fast_forward: KeyCode,
+    pub restart: KeyCode,
+    pub replay: KeyCode,
+    pub mute: KeyCode,
+}
+
+//A slot per human paddle in the order they're handed out, kept when other pads come and go
+#[derive(Default)]
+pub struct ConnectedGamepads(pub [Option<Gamepad>; 4]);
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    pub dead_zone: f32,
+}
+
+impl PaddleInputs {
+    pub fn new(axis: f32) -> Self {
+        PaddleInputs {
+            axis: axis.clamp(-1.0, 1.0),
+        }
+    }
+
+    pub fn from_buttons(up: bool, down: bool) -> Self {
+        PaddleInputs::new(up as i32 as f32 - down as i32 as f32)
+    }
+
+    pub fn combine(self, other: PaddleInputs) -> Self {
+        PaddleInputs::new(self.axis + other.axis)
+    }
+}
+
+impl ConnectedGamepads {
+    //Takes the first free slot, so a pad plugged back in picks up the paddle it left
+    pub fn connect(&mut self, gamepad: Gamepad) {
+        if self.0.contains(&Some(gamepad)) {
+            return;
+        }
+        if let Some(slot) = self.0.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(gamepad);
+        }
+    }
+
+    pub fn disconnect(&mut self, gamepad: Gamepad) {
+        for slot in self.0.iter_mut().filter(|slot| **slot == Some(gamepad)) {
+            *slot = None;
+        }
+    }
+}
+
+impl Action {
+    pub const ALL: [Action; 19] = [
+        Action::LeftUp,
+        Action::LeftDown,
+        Action::RightUp,
+        Action::RightDown,
+        Action::TopLeft,
+        Action::TopRight,
+        Action::BottomLeft,
+        Action::BottomRight,
+        Action::LeftServe,
+        Action::RightServe,
+        Action::TopServe,
+        Action::BottomServe,
+        Action::Pause,
+        Action::Serve,
+        Action::SlowMotion,
+        Action::FastForward,
+        Action::Restart,
+        Action::Replay,
+        Action::Mute,
+    ];
+
+    //Launches a ball stuck to that player's paddle
+    pub fn serve_for(player: Player) -> Action {
+        match player {
+            Player::Left => Action::LeftServe,
+            Player::Right => Action::RightServe,
+            Player::Top => Action::TopServe,
+            Player::Bottom => Action::BottomServe,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::LeftUp => "LEFT UP",
+            Action::LeftDown => "LEFT DOWN",
+            Action::RightUp => "RIGHT UP",
+            Action::RightDown => "RIGHT DOWN",
+            Action::TopLeft => "TOP LEFT",
+            Action::TopRight => "TOP RIGHT",
+            Action::BottomLeft => "BOTTOM LEFT",
+            Action::BottomRight => "BOTTOM RIGHT",
+            Action::LeftServe => "LEFT SERVE",
+            Action::RightServe => "RIGHT SERVE",
+            Action::TopServe => "TOP SERVE",
+            Action::BottomServe => "BOTTOM SERVE",
+            Action::Pause => "PAUSE",
+            Action::Serve => "SERVE",
+            Action::SlowMotion => "SLOW MO",
+            Action::FastForward => "FAST FWD",
+            Action::Restart => "RESTART",
+            Action::Replay => "REPLAY",
+            Action::Mute => "MUTE",
+        }
+    }
+}
+
+impl KeyBindings {
+    //The right paddle on the arrow keys, for two players sharing one keyboard
+    pub fn arrows() -> Self {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(Action::RightUp, KeyCode::Up);
+        bindings.rebind(Action::RightDown, KeyCode::Down);
+        //Down was the top serve, it takes the key the right paddle left behind
+        bindings.rebind(Action::TopServe, KeyCode::K);
+        bindings
+    }
+
+    pub fn key(&self, action: Action) -> KeyCode {
+        match action {
+            Action::LeftUp => self.left_up,
+            Action::LeftDown => self.left_down,
+            Action::RightUp => self.right_up,
+            Action::RightDown => self.right_down,
+            Action::TopLeft => self.top_left,
+            Action::TopRight => self.top_right,
+            Action::BottomLeft => self.bottom_left,
+            Action::BottomRight => self.bottom_right,
+            Action::LeftServe => self.left_serve,
+            Action::RightServe => self.right_serve,
+            Action::TopServe => self.top_serve,
+            Action::BottomServe => self.bottom_serve,
+            Action::Pause => self.pause,
+            Action::Serve => self.serve,
+            Action::SlowMotion => self.slow_motion,
+            Action::FastForward => self.fast_forward,
+            Action::Restart => self.restart,
+            Action::Replay => self.replay,
+            Action::Mute => self.mute,
+        }
+    }
+
+    pub fn rebind(&mut self, action: Action, key: KeyCode) {
+        let binding = match action {
+            Action::LeftUp => &mut self.left_up,
+            Action::LeftDown => &mut self.left_down,
+            Action::RightUp => &mut self.right_up,
+            Action::RightDown => &mut self.right_down,
+            Action::TopLeft => &mut self.top_left,
+            Action::TopRight => &mut self.top_right,
+            Action::BottomLeft => &mut self.bottom_left,
+            Action::BottomRight => &mut self.bottom_right,
+            Action::LeftServe => &mut self.left_serve,
+            Action::RightServe => &mut self.right_serve,
+            Action::TopServe => &mut self.top_serve,
+            Action::BottomServe => &mut self.bottom_serve,
+            Action::Pause => &mut self.pause,
+            Action::Serve => &mut self.serve,
+            Action::SlowMotion => &mut self.slow_motion,
+            Action::FastForward => &mut self.fast_forward,
+            Action::Restart => &mut self.restart,
+            Action::Replay => &mut self.replay,
+            Action::Mute => &mut self.mute,
+        };
+        *binding = key;
+    }
+
+    pub fn pressed(&self, action: Action, keys: &Input<KeyCode>) -> bool {
+        keys.pressed(self.key(action))
+    }
+
+    pub fn just_pressed(&self, action: Action, keys: &Input<KeyCode>) -> bool {
+        keys.just_pressed(self.key(action))
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            left_up: KeyCode::W,
+            left_down: KeyCode::S,
+            right_up: KeyCode::I,
+            right_down: KeyCode::K,
+            top_left: KeyCode::Left,
+            top_right: KeyCode::Right,
+            bottom_left: KeyCode::V,
+            bottom_right: KeyCode::B,
+            left_serve: KeyCode::D,
+            right_serve: KeyCode::J,
+            top_serve: KeyCode::Down,
+            bottom_serve: KeyCode::N,
+            pause: KeyCode::Escape,
+            serve: KeyCode::Space,
+            slow_motion: KeyCode::Space,
+            fast_forward: KeyCode::F,
+            restart: KeyCode::R,
+            replay: KeyCode::Z,
+            mute: KeyCode::M,
+        }
+    }
+}
+
+impl Inputs {
+    pub fn get(&self, player: Player) -> PaddleInputs {
+        match player {
+            Player::Left => self.left,
+            Player::Right => self.right,
+            Player::Top => self.top,
+            Player::Bottom => self.bottom,
+        }
+    }
+
+    pub fn get_mut(&mut self, player: Player) -> &mut PaddleInputs {
+        match player {
+            Player::Left => &mut self.left,
+            Player::Right => &mut self.right,
+            Player::Top => &mut self.top,
+            Player::Bottom => &mut self.bottom,
+        }
+    }
+}
+
+impl Default for Inputs {
+    fn default() -> Self {
+        Inputs {
+            left: PaddleInputs::new(0.0),
+            right: PaddleInputs::new(0.0),
+            top: PaddleInputs::new(0.0),
+            bottom: PaddleInputs::new(0.0),
+        }
+    }
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        GamepadConfig { dead_zone: 0.25 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ControlScheme {
+    Keyboard,
+    Mouse,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ControlsConfig {
+    pub left: ControlScheme,
+}
+
+impl Default for ControlsConfig {
+    fn default() -> Self {
+        ControlsConfig {
+            left: ControlScheme::Keyboard,
+        }
+    }
+}
+
+//World space y the cursor last moved to, cleared once the keyboard takes over
+#[derive(Default)]
+pub struct MouseTarget {
+    pub y: Option<f32>,
+}
+
+//Helpers
+fn gamepad_inputs(
+    gamepad: Gamepad,
+    player: Player,
+    buttons: &Input<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+    dead_zone: f32,
+) -> PaddleInputs {
+    //Top and bottom paddles read the horizontal stick and d-pad instead
+    let (stick_axis, positive, negative) = if player.axis() == Vec2::X {
+        (
+            GamepadAxisType::LeftStickX,
+            GamepadButtonType::DPadRight,
+            GamepadButtonType::DPadLeft,
+        )
+    } else {
+        (
+            GamepadAxisType::LeftStickY,
+            GamepadButtonType::DPadUp,
+            GamepadButtonType::DPadDown,
+        )
+    };
+
+    let stick = axes.get(GamepadAxis(gamepad, stick_axis)).unwrap_or(0.0);
+    let stick = if stick.abs() > dead_zone { stick } else { 0.0 };
+    let dpad_positive = buttons.pressed(GamepadButton(gamepad, positive));
+    let dpad_negative = buttons.pressed(GamepadButton(gamepad, negative));
+
+    PaddleInputs::new(stick).combine(PaddleInputs::from_buttons(dpad_positive, dpad_negative))
+}
+
+//Systems
+fn pause_system(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if !bindings.just_pressed(Action::Pause, &keys) {
+        return;
+    }
+
+    //Paused sits on top of Playing so the court is left untouched
+    match state.current() {
+        GameState::Playing => state.push(GameState::Paused).ok(),
+        GameState::Paused => state.pop().ok(),
+        _ => None,
+    };
+}
+
+fn gamepad_connections(
+    mut events: EventReader<GamepadEvent>,
+    mut gamepads: ResMut<ConnectedGamepads>,
+) {
+    for GamepadEvent(gamepad, event_type) in events.iter() {
+        match event_type {
+            GamepadEventType::Connected => gamepads.connect(*gamepad),
+            GamepadEventType::Disconnected => gamepads.disconnect(*gamepad),
+            _ => {}
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn input_decoder(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    gamepads: Res<ConnectedGamepads>,
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut inputs: ResMut<Inputs>,
+) {
+    inputs.left = PaddleInputs::from_buttons(
+        bindings.pressed(Action::LeftUp, &keys),
+        bindings.pressed(Action::LeftDown, &keys),
+    );
+    inputs.right = PaddleInputs::from_buttons(
+        bindings.pressed(Action::RightUp, &keys),
+        bindings.pressed(Action::RightDown, &keys),
+    );
+    inputs.top = PaddleInputs::from_buttons(
+        bindings.pressed(Action::TopRight, &keys),
+        bindings.pressed(Action::TopLeft, &keys),
+    );
+    inputs.bottom = PaddleInputs::from_buttons(
+        bindings.pressed(Action::BottomRight, &keys),
+        bindings.pressed(Action::BottomLeft, &keys),
+    );
+
+    //Gamepad slots take the human paddles in order: left, right, top, bottom
+    let ai_players = mode.ai_players();
+    let humans = Player::ALL
+        .iter()
+        .copied()
+        .filter(|player| !ai_players.contains(player));
+    let dead_zone = settings.gamepad.dead_zone;
+    for (slot, player) in gamepads.0.iter().zip(humans) {
+        let gamepad = match slot {
+            Some(gamepad) => *gamepad,
+            None => continue,
+        };
+        let pad = gamepad_inputs(gamepad, player, &buttons, &axes, dead_zone);
+        let input = inputs.get_mut(player);
+        *input = input.combine(pad);
+    }
+
+    for &player in ai_players {
+        *inputs.get_mut(player) = PaddleInputs::new(0.0);
+    }
+}
+
+fn playback_decoder(playback: Option<Res<Playback>>, mut inputs: ResMut<Inputs>) {
+    if let Some(frame) = playback.as_ref().and_then(|playback| playback.frame()) {
+        *inputs = frame.inputs;
+    }
+}
+
+fn track_cursor(
+    settings: Res<Settings>,
+    windows: Option<Res<Windows>>,
+    cameras: Query<&GlobalTransform, With<MainCamera>>,
+    mut last_cursor: Local<Option<Vec2>>,
+    mut target: ResMut<MouseTarget>,
+) {
+    if settings.controls.left != ControlScheme::Mouse {
+        return;
+    }
+
+    let window = match windows.as_ref().and_then(|windows| windows.get_primary()) {
+        Some(window) => window,
+        None => return,
+    };
+    let cursor = match window.cursor_position() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    //A resting cursor leaves the paddle to the keyboard
+    if *last_cursor == Some(cursor) {
+        return;
+    }
+    *last_cursor = Some(cursor);
+
+    //Window coordinates start at the bottom left, the camera looks at the court center
+    let screen = cursor - Vec2::new(window.width(), window.height()) / 2.0;
+    let world = match cameras.iter().next() {
+        Some(camera) => camera.compute_matrix() * screen.extend(0.0).extend(1.0),
+        None => screen.extend(0.0).extend(1.0),
+    };
+    target.y = Some(world.y);
+}
+
+fn mouse_decoder(
+    settings: Res<Settings>,
+    mut target: ResMut<MouseTarget>,
+    mut inputs: ResMut<Inputs>,
+    paddles: Query<(&Paddle, &Transform), Without<AiControlled>>,
+) {
+    //Within this many pixels of the cursor the paddle eases off instead of overshooting
+    const FOLLOW_RANGE: f32 = 32.0;
+
+    if settings.controls.left != ControlScheme::Mouse {
+        return;
+    }
+
+    //Keys or a gamepad always win, and the paddle stops chasing the old cursor spot
+    if inputs.left.axis != 0.0 {
+        target.y = None;
+        return;
+    }
+
+    let target_y = match target.y {
+        Some(y) => y,
+        None => return,
+    };
+    for (paddle, transform) in paddles.iter() {
+        if paddle.0 == Player::Left {
+            let distance = target_y - transform.translation.y;
+            inputs.left = PaddleInputs::new(distance / FOLLOW_RANGE);
+        }
+    }
+}
+
+fn handle_inputs(
+    clock: Res<SimulationClock>,
+    settings: Res<Settings>,
+    inputs: Res<Inputs>,
+    mut query: Query<(&Paddle, &PaddleAccel, &mut Velocity), Without<AiControlled>>,
+) {
+    for (paddle, accel, mut velocity) in query.iter_mut() {
+        let input = inputs.get(paddle.0);
+        let axis = paddle.0.axis();
+        let current = velocity.0.dot(axis);
+
+        //Speed up toward the held direction, or coast to a stop once released
+        let target = input.axis * settings.paddle.speed * settings.handicap.get(paddle.0).speed;
+        let reversing = input.axis * current < 0.0;
+        let rate = if input.axis == 0.0 {
+            accel.friction
+        } else if reversing {
+            //Friction helps brake the old direction so turning around stays snappy
+            accel.acceleration + accel.friction
+        } else {
+            accel.acceleration
+        };
+        //Clamping the step lands exactly on the target instead of overshooting it
+        let max_step = rate * clock.delta_seconds();
+        let difference = target - current;
+        velocity.0 += axis * difference.clamp(-max_step, max_step);
+    }
+}
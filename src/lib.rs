@@ -0,0 +1,99 @@
+use bevy::ecs::component::Component;
+use bevy::prelude::*;
+use recording::{Playback, Recorder, Session};
+use settings::Settings;
+
+pub mod ai;
+pub mod audio;
+pub mod gameplay;
+pub mod graphics;
+pub mod history;
+pub mod input;
+pub mod physics;
+pub mod powerups;
+pub mod recording;
+pub mod replay;
+pub mod settings;
+pub mod ui;
+
+pub use ai::AiPlugin;
+pub use audio::SoundPlugin;
+pub use gameplay::GameplayPlugin;
+pub use graphics::GraphicsPlugin;
+pub use history::HistoryPlugin;
+pub use input::InputPlugin;
+pub use physics::PhysicsPlugin;
+pub use powerups::PowerUpPlugin;
+pub use recording::RecordingPlugin;
+pub use replay::ReplayPlugin;
+pub use settings::SettingsPlugin;
+pub use ui::UiPlugin;
+
+//App
+pub fn build_app(headless: bool, session: Session) -> AppBuilder {
+    let mut app = App::build();
+    let replaying = session.is_replay();
+
+    if headless {
+        //Simulation only, input still goes through Input<KeyCode> so tests can press keys
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(bevy::input::InputPlugin)
+            .insert_resource(Settings::default());
+    } else {
+        //Settings come first so the window opens with the configured size and mode
+        app.insert_resource(Msaa { samples: 4 })
+            .add_plugin(SettingsPlugin)
+            .add_plugins(DefaultPlugins)
+            .add_plugin(GraphicsPlugin)
+            .add_plugin(UiPlugin)
+            .add_plugin(SoundPlugin);
+        //Watching a match again doesn't count as playing it
+        if !replaying {
+            app.add_plugin(HistoryPlugin);
+        }
+    }
+
+    //Plugins check for these to decide which systems they need
+    match session {
+        //Every match played in the window is kept to watch again, tests are left alone
+        Session::Live => {
+            if !headless {
+                app.insert_resource(Recorder::new(None));
+            }
+        }
+        Session::Record(path) => {
+            app.insert_resource(Recorder::new(Some(path)));
+        }
+        Session::Replay(recording) => {
+            app.insert_resource(Playback::new(*recording));
+        }
+    }
+
+    app.add_plugin(InputPlugin)
+        .add_plugin(PhysicsPlugin)
+        .add_plugin(AiPlugin)
+        .add_plugin(GameplayPlugin)
+        .add_plugin(PowerUpPlugin)
+        .add_plugin(ReplayPlugin)
+        .add_plugin(RecordingPlugin);
+    app
+}
+
+//States
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GameState {
+    MainMenu,
+    Playing,
+    Paused,
+    GameOver,
+    Controls,
+    History,
+    Records,
+}
+
+//Systems
+pub(crate) fn despawn_with<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,990 @@
+use crate::gameplay::{DemoMode, Player};
+use crate::settings::Settings;
+use crate::GameState;
+use bevy::core::CoreSystem;
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+//The simulation always advances in steps of this many seconds, whatever the frame rate
+pub const PHYSICS_STEP: f64 = 1.0 / 120.0;
+pub const PHYSICS_STAGE: &str = "physics";
+//Degrees off the face normal a paddle's very end sends the ball, so it never leaves near vertical
+pub const MAX_BOUNCE_ANGLE: f32 = 75.0;
+
+//Plugins
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<CourtBounds>()
+            .init_resource::<CourtShrink>()
+            .insert_resource(TimeScale::default())
+            .insert_resource(SimulationClock::default())
+            .init_resource::<Gravity>()
+            .add_event::<PaddleHitEvent>()
+            .add_event::<WallHitEvent>()
+            .add_event::<CollisionEvent>()
+            .add_system_to_stage(
+                CoreStage::First,
+                advance_clock
+                    .system()
+                    .label("advance_clock")
+                    .after(CoreSystem::Time),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                court_resizer.system().label("court_resizer"),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                fit_to_court.system().after("court_resizer"),
+            )
+            .add_stage_after(
+                CoreStage::Update,
+                PHYSICS_STAGE,
+                SystemStage::parallel().with_run_criteria(run_steps.system()),
+            )
+            .add_system_set_to_stage(
+                PHYSICS_STAGE,
+                SystemSet::new()
+                    .with_run_criteria(playing.system())
+                    .with_system(store_previous.system().before("detect_paddle_collisions"))
+                    .with_system(start_step.system().before("detect_paddle_collisions"))
+                    .with_system(apply_gravity.system().before("detect_paddle_collisions"))
+                    .with_system(
+                        curve_spinning_balls
+                            .system()
+                            .before("detect_paddle_collisions"),
+                    )
+                    .with_system(movement.system().label("movement"))
+                    .with_system(
+                        clamp_paddles
+                            .system()
+                            .label("clamp_paddles")
+                            .after("movement"),
+                    )
+                    .with_system(carry_stuck_balls.system().after("clamp_paddles"))
+                    .with_system(ball_wall_collision.system().after("movement"))
+                    .with_system(
+                        bounce_obstacles
+                            .system()
+                            .label("bounce_obstacles")
+                            .after("movement"),
+                    )
+                    .with_system(
+                        detect_paddle_collisions
+                            .system()
+                            .label("detect_paddle_collisions")
+                            .before("paddle_collision"),
+                    )
+                    .with_system(
+                        paddle_collision_response
+                            .system()
+                            .label("paddle_collision")
+                            .before("movement"),
+                    )
+                    .with_system(
+                        detect_obstacle_collisions
+                            .system()
+                            .label("detect_obstacle_collisions")
+                            .after("paddle_collision"),
+                    )
+                    .with_system(
+                        obstacle_collision_response
+                            .system()
+                            .after("detect_obstacle_collisions")
+                            .before("movement"),
+                    ),
+            );
+    }
+}
+
+//Resources
+pub struct CourtBounds {
+    pub half_width: f32,
+    pub half_height: f32,
+}
+
+impl CourtBounds {
+    pub fn half_size(&self) -> Vec2 {
+        Vec2::new(self.half_width, self.half_height)
+    }
+
+    //Paddles keep the same distance from their edge whatever the window size
+    pub fn paddle_position(&self, player: Player, edge_offset: f32) -> Vec2 {
+        let normal = player.goal_normal();
+        let half_extent = self.half_size().dot(normal.abs());
+        normal * (half_extent - edge_offset).max(0.0)
+    }
+}
+
+impl Default for CourtBounds {
+    fn default() -> Self {
+        CourtBounds {
+            half_width: 640.0,
+            half_height: 360.0,
+        }
+    }
+}
+
+//How much of the window the court fills, sudden death closes it in. It never shrinks below
+//min_half_size, or past the full size on either axis
+pub struct CourtShrink {
+    pub scale: f32,
+    pub min_half_size: Vec2,
+}
+
+impl CourtShrink {
+    pub fn apply(&self, full: Vec2) -> Vec2 {
+        (full * self.scale).max(self.min_half_size).min(full)
+    }
+}
+
+impl Default for CourtShrink {
+    fn default() -> Self {
+        CourtShrink {
+            scale: 1.0,
+            min_half_size: Vec2::ZERO,
+        }
+    }
+}
+
+//How fast the simulation runs compared to real time, slow motion lowers it
+pub struct TimeScale(pub f32);
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        TimeScale(1.0)
+    }
+}
+
+//Whole physics steps the simulation moves this frame. Everything that plays the match counts
+//time in these instead of real time, so a recording's frames come out the same on replay
+#[derive(Default)]
+pub struct SimulationClock {
+    pub steps: u32,
+    accumulator: f64,
+}
+
+impl SimulationClock {
+    pub fn delta(&self) -> Duration {
+        Duration::from_secs_f64(self.steps as f64 * PHYSICS_STEP)
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta().as_secs_f32()
+    }
+
+    //How far real time has got into the next step, for drawing in between
+    pub fn overstep(&self) -> f32 {
+        (self.accumulator / PHYSICS_STEP) as f32
+    }
+}
+
+//Constant pull on the ball in units per second squared, zero for the classic straight lines
+#[derive(Default)]
+pub struct Gravity(pub Vec2);
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GravityConfig {
+    pub enabled: bool,
+    pub acceleration: [f32; 2],
+}
+
+impl GravityConfig {
+    pub fn gravity(&self) -> Gravity {
+        if self.enabled {
+            Gravity(Vec2::from(self.acceleration))
+        } else {
+            Gravity::default()
+        }
+    }
+}
+
+impl Default for GravityConfig {
+    fn default() -> Self {
+        GravityConfig {
+            enabled: false,
+            acceleration: [0.0, -200.0],
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ObstacleConfig {
+    pub enabled: bool,
+    pub speed: f32,
+    //How far above and below the center line the obstacle travels
+    pub range: f32,
+    pub width: f32,
+    pub height: f32,
+    //The arcade variant swaps the drifting obstacle for fixed blocks laid out around the center
+    pub arcade: bool,
+    pub layout: Vec<ObstacleBlock>,
+}
+
+//Position is from the court's center
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ObstacleBlock {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for ObstacleConfig {
+    fn default() -> Self {
+        ObstacleConfig {
+            enabled: false,
+            speed: 120.0,
+            range: 150.0,
+            width: 16.0,
+            height: 80.0,
+            arcade: false,
+            layout: vec![
+                ObstacleBlock {
+                    x: 0.0,
+                    y: 0.0,
+                    width: 24.0,
+                    height: 72.0,
+                },
+                ObstacleBlock {
+                    x: -120.0,
+                    y: 180.0,
+                    width: 24.0,
+                    height: 56.0,
+                },
+                ObstacleBlock {
+                    x: 120.0,
+                    y: -180.0,
+                    width: 24.0,
+                    height: 56.0,
+                },
+            ],
+        }
+    }
+}
+
+//Events
+pub struct PaddleHitEvent {
+    pub ball: Entity,
+    pub player: Player,
+    pub position: Vec2,
+    pub ball_speed: f32,
+    pub hit_offset: f32,
+}
+
+//A ball touching a paddle or block during a physics step, entity_a is always the ball. The
+//responses bounce it from where it touched, time is how far through the step that was.
+//closing_speed is zero or less when the ball was only pushed out of an overlap it was leaving
+#[derive(Clone, Copy, Debug)]
+pub struct CollisionEvent {
+    pub entity_a: Entity,
+    pub entity_b: Entity,
+    pub normal: Vec2,
+    pub position: Vec2,
+    pub time: f32,
+    pub closing_speed: f32,
+}
+
+impl CollisionEvent {
+    //Sounds and effects only follow real hits, not push-outs
+    pub fn is_impact(&self) -> bool {
+        self.closing_speed > 0.0
+    }
+}
+
+pub struct WallHitEvent {
+    pub side: WallSide,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WallSide {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    Shield(Player),
+}
+
+//Components
+pub struct Ball;
+pub struct Paddle(pub Player);
+pub struct Velocity(pub Vec2);
+pub struct BallSpeed(pub f32);
+//Angular velocity in radians per second, counterclockwise is positive
+pub struct Spin(pub f32);
+pub struct PaddleSize(pub Vec2);
+//How long the paddle is without any power-up, goals can shorten it in shrinking paddle matches
+pub struct PaddleLength(pub f32);
+
+//A block the ball bounces off, the drifting one also has a Velocity
+pub struct Obstacle {
+    pub size: Vec2,
+}
+
+//Where the entity was before the latest physics step, so rendering can blend between steps
+pub struct Interpolated {
+    pub previous: Vec3,
+}
+
+//Where the ball's travel this physics step begins and how far into the step that is. A bounce
+//off a paddle or block moves it to the contact, so the walls are swept from the real path
+pub struct StepStart {
+    pub position: Vec2,
+    pub time: f32,
+}
+
+//A paddle that catches the ball instead of returning it
+pub struct Sticky;
+
+//A ball held on a paddle's face, it rides along with the paddle until it's launched
+pub struct StuckTo(pub Entity);
+
+//Where along the paddle a stuck ball sits, and how long until it launches by itself
+pub struct StickHold {
+    pub offset: f32,
+    pub timer: Timer,
+}
+
+impl StickHold {
+    pub const SECONDS: f32 = 2.0;
+}
+
+pub struct PaddleAccel {
+    pub acceleration: f32,
+    pub friction: f32,
+}
+
+//Helpers
+//Tilts the face normal toward the paddle's axis the further off center the hit lands
+pub fn bounce_direction(hit_offset: f32, normal: Vec2, axis: Vec2) -> Vec2 {
+    let angle = hit_offset.clamp(-1.0, 1.0) * MAX_BOUNCE_ANGLE.to_radians();
+    normal * angle.cos() + axis * angle.sin()
+}
+
+pub fn reflect(velocity: Vec2, normal: Vec2) -> Vec2 {
+    velocity - 2.0 * velocity.dot(normal) * normal
+}
+
+//Moving something outside a physics step is a jump, not motion to blend across
+pub(crate) fn snap_interpolation(transform: &Transform, interpolated: Option<Mut<Interpolated>>) {
+    if let Some(mut interpolated) = interpolated {
+        interpolated.previous = transform.translation;
+    }
+}
+
+//Earliest time in [0, 1] the segment start..start + delta enters the box, with the face normal
+//Starting inside the box isn't an entry, overlaps are handled separately
+pub fn sweep_box(start: Vec2, delta: Vec2, center: Vec2, half_size: Vec2) -> Option<(f32, Vec2)> {
+    let axes = [
+        (start.x, delta.x, center.x, half_size.x, Vec2::new(1.0, 0.0)),
+        (start.y, delta.y, center.y, half_size.y, Vec2::new(0.0, 1.0)),
+    ];
+
+    let mut enter = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut normal = Vec2::ZERO;
+    for (start, delta, center, half_size, axis) in axes {
+        let (min, max) = (center - half_size, center + half_size);
+        if delta == 0.0 {
+            //Parallel to this slab, so it has to already be between its faces
+            if start < min || start > max {
+                return None;
+            }
+            continue;
+        }
+
+        let (near, far) = if delta > 0.0 {
+            ((min - start) / delta, (max - start) / delta)
+        } else {
+            ((max - start) / delta, (min - start) / delta)
+        };
+        if near > enter {
+            enter = near;
+            normal = axis * -delta.signum();
+        }
+        exit = exit.min(far);
+    }
+
+    if enter > exit || enter < 0.0 || enter > 1.0 {
+        None
+    } else {
+        Some((enter, normal))
+    }
+}
+
+//Where a ball, swept as a point, first touches a box already grown by its radius, as
+//(time, position, normal). Balls that start inside are pushed out the shallow side at time 0
+pub fn ball_box_contact(
+    start: Vec2,
+    delta: Vec2,
+    center: Vec2,
+    half_size: Vec2,
+) -> Option<(f32, Vec2, Vec2)> {
+    if let Some((time, normal)) = sweep_box(start, delta, center, half_size) {
+        return Some((time, start + delta * time, normal));
+    }
+
+    let offset = start - center;
+    let depth = half_size - offset.abs();
+    if depth.x <= 0.0 || depth.y <= 0.0 {
+        None
+    } else if depth.x < depth.y {
+        let normal = Vec2::new(offset.x.signum(), 0.0);
+        Some((0.0, start + normal * depth.x, normal))
+    } else {
+        let normal = Vec2::new(0.0, offset.y.signum());
+        Some((0.0, start + normal * depth.y, normal))
+    }
+}
+
+//Moves a ball for dt seconds inside the walls, stopping at the first wall it reaches, bouncing
+//off it and carrying on for what's left of the step. limit is how far the ball's center can go
+//from the middle, sides missing from walls are open. Returns the end position and velocity,
+//plus the walls hit in order
+pub fn sweep_walls(
+    start: Vec2,
+    velocity: Vec2,
+    dt: f32,
+    limit: Vec2,
+    walls: &[Player],
+) -> (Vec2, Vec2, Vec<Player>) {
+    //Two walls meet in a corner, so more bounces than that in one step means it's stuck
+    const MAX_BOUNCES: usize = 4;
+
+    let (mut position, mut velocity) = (start, velocity);
+    let mut remaining = dt;
+    let mut hit = Vec::new();
+    for _ in 0..MAX_BOUNCES {
+        let mut earliest: Option<(f32, Player)> = None;
+        for &side in walls {
+            let normal = side.goal_normal();
+            let approach = velocity.dot(normal);
+            if approach <= 0.0 {
+                continue;
+            }
+            let time = ((limit.dot(normal.abs()) - position.dot(normal)) / approach).max(0.0);
+            if time <= remaining && earliest.map_or(true, |(best, _)| time < best) {
+                earliest = Some((time, side));
+            }
+        }
+
+        let (time, side) = match earliest {
+            Some(contact) => contact,
+            None => break,
+        };
+        position += velocity * time;
+        velocity = reflect(velocity, -side.goal_normal());
+        remaining -= time;
+        hit.push(side);
+    }
+    (position + velocity * remaining, velocity, hit)
+}
+
+//Systems
+fn advance_clock(time: Res<Time>, mut clock: ResMut<SimulationClock>) {
+    clock.accumulator += time.delta_seconds_f64();
+    let steps = (clock.accumulator / PHYSICS_STEP).floor();
+    clock.accumulator -= steps * PHYSICS_STEP;
+    clock.steps = steps as u32;
+}
+
+//Runs the physics stage once for every step on the clock
+fn run_steps(clock: Res<SimulationClock>, mut done: Local<u32>) -> ShouldRun {
+    if *done < clock.steps {
+        *done += 1;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        *done = 0;
+        ShouldRun::No
+    }
+}
+
+//Matches and the menu's demo both need the simulation
+pub(crate) fn playing(state: Res<State<GameState>>, demo: Option<Res<DemoMode>>) -> ShouldRun {
+    let demo = demo.is_some() && *state.current() == GameState::MainMenu;
+    if *state.current() == GameState::Playing || demo {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn store_previous(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in query.iter_mut() {
+        interpolated.previous = transform.translation;
+    }
+}
+
+fn start_step(mut query: Query<(&Transform, &mut StepStart)>) {
+    for (transform, mut start) in query.iter_mut() {
+        start.position = transform.translation.truncate();
+        start.time = 0.0;
+    }
+}
+
+//Runs before the paddle sweep so it tests the same path movement then takes
+fn apply_gravity(
+    gravity: Res<Gravity>,
+    time_scale: Res<TimeScale>,
+    mut query: Query<&mut Velocity, With<Ball>>,
+) {
+    let dt = PHYSICS_STEP as f32 * time_scale.0;
+
+    for mut velocity in query.iter_mut() {
+        //A ball waiting to be served stays put
+        if velocity.0 != Vec2::ZERO {
+            velocity.0 += gravity.0 * dt;
+        }
+    }
+}
+
+//The Magnus effect pushes a spinning ball sideways, bending its path without changing its speed
+fn curve_spinning_balls(
+    settings: Res<Settings>,
+    time_scale: Res<TimeScale>,
+    mut query: Query<(&mut Velocity, &mut Spin), With<Ball>>,
+) {
+    let dt = PHYSICS_STEP as f32 * time_scale.0;
+    let config = &settings.ball;
+
+    for (mut velocity, mut spin) in query.iter_mut() {
+        if spin.0 == 0.0 || velocity.0 == Vec2::ZERO {
+            continue;
+        }
+
+        //coefficient * spin * |v| along the unit perpendicular, which is just v.perp() scaled
+        let speed = velocity.0.length();
+        let lateral = velocity.0.perp() * config.spin_coefficient * spin.0;
+        velocity.0 = (velocity.0 + lateral * dt).normalize() * speed;
+
+        spin.0 *= (-config.spin_decay * dt).exp();
+    }
+}
+
+//Stuck balls go wherever their paddle takes them instead
+fn movement(
+    time_scale: Res<TimeScale>,
+    mut query: Query<(&mut Transform, &Velocity), Without<StuckTo>>,
+) {
+    let dt = PHYSICS_STEP as f32 * time_scale.0;
+
+    for (mut transform, velocity) in query.iter_mut() {
+        transform.translation += velocity.0.extend(0.0) * dt;
+    }
+}
+
+fn clamp_paddles(
+    bounds: Res<CourtBounds>,
+    mut query: Query<(&Paddle, &PaddleSize, &mut Transform, &mut Velocity)>,
+) {
+    for (paddle, size, mut transform, mut velocity) in query.iter_mut() {
+        //Stop the paddle at the edge so it doesn't keep pushing past it
+        let axis = paddle.0.axis();
+        let limit = bounds.half_size().dot(axis) - size.0.dot(axis) / 2.0;
+        let position = transform.translation.truncate().dot(axis);
+        if position.abs() > limit {
+            let clamped = position.clamp(-limit, limit);
+            transform.translation += (axis * (clamped - position)).extend(0.0);
+            let speed = velocity.0.dot(axis);
+            velocity.0 -= axis * speed;
+        }
+    }
+}
+
+//After the paddles are clamped, so a ball can't be carried past the end of the court
+fn carry_stuck_balls(
+    paddles: Query<(&Paddle, &Transform), Without<Ball>>,
+    mut balls: Query<(&StuckTo, &StickHold, &mut Transform), With<Ball>>,
+) {
+    for (stuck, hold, mut transform) in balls.iter_mut() {
+        if let Ok((paddle, paddle_transform)) = paddles.get(stuck.0) {
+            let axis = paddle.0.axis();
+            let along = paddle_transform.translation.truncate().dot(axis) + hold.offset;
+            let position = transform.translation.truncate();
+            let carried = position + axis * (along - position.dot(axis));
+            transform.translation = carried.extend(transform.translation.z);
+        }
+    }
+}
+
+fn court_resizer(
+    windows: Option<Res<Windows>>,
+    shrink: Res<CourtShrink>,
+    mut unshrunk: Local<Option<Vec2>>,
+    mut bounds: ResMut<CourtBounds>,
+) {
+    let window = windows
+        .as_ref()
+        .and_then(|windows| windows.get_primary())
+        .map(|window| Vec2::new(window.width(), window.height()) / 2.0);
+    let full = match window {
+        Some(size) => size,
+        //Headless apps have no window and keep their court, unless sudden death shrinks it
+        None if shrink.is_changed() => *unshrunk.get_or_insert(bounds.half_size()),
+        None => return,
+    };
+    if shrink.scale >= 1.0 {
+        *unshrunk = None;
+    }
+
+    let half_size = shrink.apply(full);
+    if bounds.half_size() != half_size {
+        bounds.half_width = half_size.x;
+        bounds.half_height = half_size.y;
+    }
+}
+
+fn fit_to_court(
+    settings: Res<Settings>,
+    bounds: Res<CourtBounds>,
+    mut paddles: Query<(&Paddle, &mut Transform, Option<&mut Interpolated>), Without<Ball>>,
+    mut balls: Query<(&mut Transform, Option<&mut Interpolated>), With<Ball>>,
+) {
+    if !bounds.is_changed() {
+        return;
+    }
+
+    for (paddle, mut transform, interpolated) in paddles.iter_mut() {
+        //Keep the paddle's place along its axis, only its distance from the edge changes
+        let axis = paddle.0.axis();
+        let along = axis * transform.translation.truncate().dot(axis);
+        let position = along + bounds.paddle_position(paddle.0, settings.paddle.edge_offset);
+        transform.translation = position.extend(transform.translation.z);
+        snap_interpolation(&transform, interpolated);
+    }
+
+    //Shrinking the window shouldn't score a goal or strand the ball behind a wall
+    let radius = settings.ball.radius;
+    let limit = Vec2::new(bounds.half_width - radius, bounds.half_height - radius).max(Vec2::ZERO);
+    for (mut transform, interpolated) in balls.iter_mut() {
+        let position = transform.translation.truncate().clamp(-limit, limit);
+        transform.translation = position.extend(transform.translation.z);
+        snap_interpolation(&transform, interpolated);
+    }
+}
+
+fn ball_wall_collision(
+    settings: Res<Settings>,
+    bounds: Res<CourtBounds>,
+    time_scale: Res<TimeScale>,
+    mut hits: EventWriter<WallHitEvent>,
+    mut query: Query<(&mut Transform, &mut Velocity, &StepStart), With<Ball>>,
+    paddles: Query<&Paddle>,
+) {
+    //A side with a paddle is a goal instead of a wall
+    let walls: Vec<Player> = Player::ALL
+        .iter()
+        .copied()
+        .filter(|side| !paddles.iter().any(|paddle| paddle.0 == *side))
+        .collect();
+
+    let limit = bounds.half_size() - Vec2::splat(settings.ball.radius);
+    let dt = PHYSICS_STEP as f32 * time_scale.0;
+    for (mut transform, mut velocity, step_start) in query.iter_mut() {
+        //Retrace the step from where the ball really set off, or last bounced, so a fast ball
+        //bounces where it actually met the wall instead of wherever it ended up past it
+        let mut start = step_start.position;
+        let remaining = dt * (1.0 - step_start.time);
+        let mut bounced = Vec::new();
+
+        //Already out at the start of the step, e.g. a paddle pushed it there, just clamp it back
+        for &side in &walls {
+            let normal = side.goal_normal();
+            let overshoot = start.dot(normal) - limit.dot(normal.abs());
+            if overshoot > 0.0 {
+                start -= normal * overshoot;
+                if velocity.0.dot(normal) > 0.0 {
+                    velocity.0 = reflect(velocity.0, -normal);
+                    bounced.push(side);
+                }
+            }
+        }
+
+        let (end, reflected, swept) = sweep_walls(start, velocity.0, remaining, limit, &walls);
+        if bounced.is_empty() && swept.is_empty() {
+            continue;
+        }
+        transform.translation = end.extend(transform.translation.z);
+        velocity.0 = reflected;
+
+        for side in bounced.into_iter().chain(swept) {
+            hits.send(WallHitEvent {
+                side: match side {
+                    Player::Left => WallSide::Left,
+                    Player::Right => WallSide::Right,
+                    Player::Top => WallSide::Top,
+                    Player::Bottom => WallSide::Bottom,
+                },
+            });
+        }
+    }
+}
+
+fn bounce_obstacles(
+    settings: Res<Settings>,
+    mut query: Query<(&mut Transform, &mut Velocity), With<Obstacle>>,
+) {
+    let range = settings.obstacle.range;
+    for (mut transform, mut velocity) in query.iter_mut() {
+        let y = transform.translation.y;
+        if y.abs() > range {
+            transform.translation.y = y.clamp(-range, range);
+            //Turn back toward the center, like the ball off a wall
+            if velocity.0.y * y > 0.0 {
+                velocity.0.y = -velocity.0.y;
+            }
+        }
+    }
+}
+
+//Sweeping the ball's path for the step rather than testing overlap at its end keeps fast
+//balls from passing straight through a paddle between two steps
+pub fn detect_paddle_collisions(
+    settings: Res<Settings>,
+    time_scale: Res<TimeScale>,
+    mut collisions: EventWriter<CollisionEvent>,
+    balls: Query<(Entity, &Transform, &Velocity), With<Ball>>,
+    paddles: Query<(Entity, &Transform, &PaddleSize), Without<Ball>>,
+) {
+    let radius = settings.ball.radius;
+    let dt = PHYSICS_STEP as f32 * time_scale.0;
+
+    for (ball, ball_transform, ball_velocity) in balls.iter() {
+        let ball_pos = ball_transform.translation.truncate();
+        let delta = ball_velocity.0 * dt;
+
+        let mut earliest: Option<CollisionEvent> = None;
+        for (paddle, paddle_transform, paddle_size) in paddles.iter() {
+            //Growing the paddle by the radius lets the ball be swept as a point
+            let center = paddle_transform.translation.truncate();
+            let half_size = paddle_size.0 / 2.0 + Vec2::splat(radius);
+
+            //Overlaps come from a paddle moving onto the ball
+            let hit = ball_box_contact(ball_pos, delta, center, half_size);
+            if let Some((time, position, normal)) = hit {
+                if earliest.as_ref().map_or(true, |best| time < best.time) {
+                    earliest = Some(CollisionEvent {
+                        entity_a: ball,
+                        entity_b: paddle,
+                        normal,
+                        position,
+                        time,
+                        closing_speed: -ball_velocity.0.dot(normal),
+                    });
+                }
+            }
+        }
+
+        if let Some(collision) = earliest {
+            collisions.send(collision);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn paddle_collision_response(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    time_scale: Res<TimeScale>,
+    mut collisions: EventReader<CollisionEvent>,
+    mut hits: EventWriter<PaddleHitEvent>,
+    mut balls: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &mut BallSpeed,
+            &mut Spin,
+            &mut StepStart,
+        ),
+        With<Ball>,
+    >,
+    paddles: Query<(&Paddle, &Transform, &PaddleSize, &Velocity, Option<&Sticky>), Without<Ball>>,
+) {
+    let radius = settings.ball.radius;
+    let dt = PHYSICS_STEP as f32 * time_scale.0;
+
+    for collision in collisions.iter() {
+        let (paddle, paddle_transform, paddle_size, paddle_velocity, sticky) =
+            match paddles.get(collision.entity_b) {
+                Ok(paddle) => paddle,
+                Err(_) => continue,
+            };
+        let (mut ball_transform, mut ball_velocity, mut ball_speed, mut ball_spin, mut step_start) =
+            match balls.get_mut(collision.entity_a) {
+                Ok(ball) => ball,
+                Err(_) => continue,
+            };
+        let CollisionEvent {
+            normal,
+            position: contact,
+            time,
+            ..
+        } = *collision;
+
+        //Don't re-flip if already moving away, just make sure it's outside
+        if ball_velocity.0.dot(normal) >= 0.0 {
+            if time == 0.0 {
+                ball_transform.translation = contact.extend(ball_transform.translation.z);
+                step_start.position = contact;
+            }
+            continue;
+        }
+
+        //Face hits aim by where they land along the paddle, edge hits just reflect
+        let player = paddle.0;
+        let axis = player.axis();
+        let paddle_pos = paddle_transform.translation.truncate();
+        let half_size = paddle_size.0 / 2.0;
+        let hit_offset = ((contact - paddle_pos).dot(axis) / half_size.dot(axis)).clamp(-1.0, 1.0);
+        let direction = if normal.dot(axis) == 0.0 {
+            bounce_direction(hit_offset, normal, axis)
+        } else {
+            reflect(ball_velocity.0, normal).normalize()
+        };
+
+        //Speed up every return, tracked apart from the direction
+        let max_speed = settings.ball.max_speed.max(ball_speed.0);
+        ball_speed.0 = (ball_speed.0 * settings.ball.speed_multiplier).min(max_speed);
+        ball_velocity.0 = direction * ball_speed.0;
+
+        //A sticky face catches the ball, it's launched later at the speed it would have left with
+        if sticky.is_some() && normal.dot(axis) == 0.0 {
+            ball_velocity.0 = Vec2::ZERO;
+            ball_spin.0 = 0.0;
+            ball_transform.translation = contact.extend(ball_transform.translation.z);
+            commands
+                .entity(collision.entity_a)
+                .insert(StuckTo(collision.entity_b))
+                .insert(StickHold {
+                    offset: (contact - paddle_pos).dot(axis),
+                    timer: Timer::from_seconds(StickHold::SECONDS, false),
+                });
+            hits.send(PaddleHitEvent {
+                ball: collision.entity_a,
+                player,
+                position: contact - normal * radius,
+                ball_speed: ball_speed.0,
+                hit_offset,
+            });
+            continue;
+        }
+
+        //A moving paddle drags the ball along, a still one leaves the rebound alone
+        let spin = paddle_velocity.0.dot(axis) * settings.ball.spin;
+        if spin != 0.0 {
+            ball_velocity.0 += axis * spin;
+            ball_velocity.0 = ball_velocity.0.clamp_length_max(max_speed);
+        }
+        //The paddle's face rubs against the side of the ball it touches, setting it spinning
+        ball_spin.0 =
+            (-normal).perp_dot(paddle_velocity.0.dot(axis) * axis) * settings.ball.spin_transfer;
+
+        //Movement runs next and moves the whole step, so back up by the part spent before contact
+        let resolved = contact - ball_velocity.0 * dt * time;
+        ball_transform.translation = resolved.extend(ball_transform.translation.z);
+        step_start.position = contact;
+        step_start.time = time;
+
+        hits.send(PaddleHitEvent {
+            ball: collision.entity_a,
+            player,
+            position: contact - normal * radius,
+            ball_speed: ball_speed.0,
+            hit_offset,
+        });
+    }
+}
+
+//Swept the same way as the paddles, so fast balls can't tunnel through a block either
+pub fn detect_obstacle_collisions(
+    settings: Res<Settings>,
+    time_scale: Res<TimeScale>,
+    mut collisions: EventWriter<CollisionEvent>,
+    obstacles: Query<(Entity, &Obstacle, &Transform), Without<Ball>>,
+    balls: Query<(Entity, &Transform, &Velocity), With<Ball>>,
+) {
+    let radius = settings.ball.radius;
+    let dt = PHYSICS_STEP as f32 * time_scale.0;
+
+    for (ball, transform, velocity) in balls.iter() {
+        //A ball waiting to be served is left where it is
+        if velocity.0 == Vec2::ZERO {
+            continue;
+        }
+
+        let ball_pos = transform.translation.truncate();
+        let delta = velocity.0 * dt;
+        let earliest = obstacles
+            .iter()
+            .filter_map(|(obstacle, block, obstacle_transform)| {
+                let center = obstacle_transform.translation.truncate();
+                let half_size = block.size / 2.0 + Vec2::splat(radius);
+                ball_box_contact(ball_pos, delta, center, half_size).map(
+                    |(time, position, normal)| CollisionEvent {
+                        entity_a: ball,
+                        entity_b: obstacle,
+                        normal,
+                        position,
+                        time,
+                        closing_speed: -velocity.0.dot(normal),
+                    },
+                )
+            })
+            .min_by(|a, b| {
+                a.time
+                    .partial_cmp(&b.time)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+
+        if let Some(collision) = earliest {
+            collisions.send(collision);
+        }
+    }
+}
+
+fn obstacle_collision_response(
+    time_scale: Res<TimeScale>,
+    mut collisions: EventReader<CollisionEvent>,
+    obstacles: Query<&Obstacle>,
+    mut balls: Query<(&mut Transform, &mut Velocity, &mut StepStart), With<Ball>>,
+) {
+    let dt = PHYSICS_STEP as f32 * time_scale.0;
+
+    for collision in collisions.iter() {
+        if obstacles.get(collision.entity_b).is_err() {
+            continue;
+        }
+        let (mut transform, mut velocity, mut step_start) = match balls.get_mut(collision.entity_a)
+        {
+            Ok(ball) => ball,
+            Err(_) => continue,
+        };
+        let CollisionEvent {
+            normal,
+            position: contact,
+            time,
+            ..
+        } = *collision;
+
+        if velocity.0.dot(normal) >= 0.0 {
+            if time == 0.0 {
+                transform.translation = contact.extend(transform.translation.z);
+                step_start.position = contact;
+            }
+            continue;
+        }
+
+        velocity.0 = reflect(velocity.0, normal);
+        //Movement runs next and moves the whole step, so back up by the part spent before contact
+        let resolved = contact - velocity.0 * dt * time;
+        transform.translation = resolved.extend(transform.translation.z);
+        step_start.position = contact;
+        step_start.time = time;
+    }
+}
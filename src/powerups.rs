@@ -0,0 +1,449 @@
+use crate::gameplay::{paddle_size, GoalEvent, InGame, Player, RestartEvent, ServeRng};
+use crate::input::{Action, KeyBindings};
+use crate::physics::{
+    self, Ball, BallSpeed, CourtBounds, Paddle, PaddleHitEvent, PaddleLength, PaddleSize,
+    SimulationClock, StickHold, Sticky, StuckTo, Velocity, WallHitEvent, WallSide, PHYSICS_STAGE,
+};
+use crate::settings::Settings;
+use crate::GameState;
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+pub const SHIELD_WIDTH: f32 = 6.0;
+
+//Plugins
+pub struct PowerUpPlugin;
+
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(PowerUpSpawner::default())
+            .insert_resource(LastHitter(None))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_power_ups.system().label("reset_power_ups")),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(spawn_power_ups.system().after("restart_point"))
+                    .with_system(track_last_hitter.system().label("track_last_hitter"))
+                    .with_system(
+                        collect_power_ups
+                            .system()
+                            .label("collect_power_ups")
+                            .after("track_last_hitter"),
+                    )
+                    .with_system(expire_effects.system().after("collect_power_ups"))
+                    .with_system(update_shields.system().after("collect_power_ups"))
+                    .with_system(cancel_power_ups.system().after("restart_point"))
+                    .with_system(release_stuck_balls.system()),
+            )
+            .add_system_set_to_stage(
+                PHYSICS_STAGE,
+                SystemSet::new()
+                    .with_run_criteria(physics::playing.system())
+                    .with_system(shield_collision.system().after("movement")),
+            );
+    }
+}
+
+//Resources
+//The next pickup to appear and the countdown until it does
+pub struct PowerUpSpawner {
+    pub kind: PowerUpKind,
+    pub spawn_timer: Timer,
+}
+
+impl Default for PowerUpSpawner {
+    fn default() -> Self {
+        PowerUpSpawner {
+            kind: PowerUpKind::BigPaddle,
+            spawn_timer: Timer::from_seconds(0.0, false),
+        }
+    }
+}
+
+//Who the ball came off last, they're the one a pickup rewards
+pub struct LastHitter(pub Option<Player>);
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PowerUpConfig {
+    //Seconds between pickups, 0 turns power-ups off
+    pub interval: f32,
+    pub duration: f32,
+    pub size: f32,
+}
+
+impl Default for PowerUpConfig {
+    fn default() -> Self {
+        PowerUpConfig {
+            interval: 10.0,
+            duration: 5.0,
+            size: 12.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerUpKind {
+    BigPaddle,
+    NarrowOpponentPaddle,
+    SlowBall,
+    FastBall,
+    Shield,
+    //The collector's paddle catches the ball and holds it until they serve it back
+    StickyPaddle,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 6] = [
+        PowerUpKind::BigPaddle,
+        PowerUpKind::NarrowOpponentPaddle,
+        PowerUpKind::SlowBall,
+        PowerUpKind::FastBall,
+        PowerUpKind::Shield,
+        PowerUpKind::StickyPaddle,
+    ];
+
+    pub fn random(rng: &mut impl Rng) -> Self {
+        PowerUpKind::ALL[rng.gen_range(0..PowerUpKind::ALL.len())]
+    }
+
+    //How much a paddle effect scales the paddle length
+    fn paddle_factor(self) -> f32 {
+        match self {
+            PowerUpKind::BigPaddle => 1.5,
+            PowerUpKind::NarrowOpponentPaddle => 0.6,
+            _ => 1.0,
+        }
+    }
+
+    //How much a ball effect scales the ball speed, a slow ball slows time instead
+    fn ball_factor(self) -> f32 {
+        match self {
+            PowerUpKind::FastBall => 1.4,
+            _ => 1.0,
+        }
+    }
+}
+
+//Components
+pub struct PowerUp(pub PowerUpKind);
+
+pub struct ActiveEffect {
+    pub kind: PowerUpKind,
+    pub timer: Timer,
+}
+
+//A temporary wall guarding the owner's goal
+pub struct Shield {
+    pub owner: Player,
+    pub timer: Timer,
+}
+
+//Helpers
+//A shield's width out in front of the owner's paddle, on the court side of its face
+pub fn shield_position(bounds: &CourtBounds, settings: &Settings, owner: Player) -> Vec2 {
+    let paddle = bounds.paddle_position(owner, settings.paddle.edge_offset);
+    let face = paddle - owner.goal_normal() * settings.paddle.width / 2.0;
+    face - owner.goal_normal() * SHIELD_WIDTH
+}
+
+//Systems
+fn reset_power_ups(
+    settings: Res<Settings>,
+    mut rng: ResMut<ServeRng>,
+    mut spawner: ResMut<PowerUpSpawner>,
+    mut last_hitter: ResMut<LastHitter>,
+) {
+    last_hitter.0 = None;
+    spawner.kind = PowerUpKind::random(&mut rng.0);
+    spawner.spawn_timer = Timer::from_seconds(settings.powerups.interval, false);
+}
+
+fn spawn_power_ups(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    settings: Res<Settings>,
+    bounds: Res<CourtBounds>,
+    mut rng: ResMut<ServeRng>,
+    mut spawner: ResMut<PowerUpSpawner>,
+    pickups: Query<(), With<PowerUp>>,
+) {
+    //One pickup at a time, the next countdown starts once it's collected
+    if settings.powerups.interval <= 0.0 || pickups.iter().next().is_some() {
+        return;
+    }
+    if !spawner.spawn_timer.tick(clock.delta()).finished() {
+        return;
+    }
+
+    //Anywhere between the paddles, clear of the walls
+    let margin = settings.powerups.size * 2.0;
+    let paddle_x = bounds
+        .paddle_position(Player::Right, settings.paddle.edge_offset)
+        .x;
+    let range_x = (paddle_x - margin).max(0.0);
+    let range_y = (bounds.half_height - margin).max(0.0);
+    let x = rng.0.gen_range(-range_x..=range_x);
+    let y = rng.0.gen_range(-range_y..=range_y);
+    commands
+        .spawn_bundle((Transform::from_xyz(x, y, 0.0), GlobalTransform::default()))
+        .insert(InGame)
+        .insert(PowerUp(spawner.kind));
+
+    spawner.kind = PowerUpKind::random(&mut rng.0);
+    spawner.spawn_timer = Timer::from_seconds(settings.powerups.interval, false);
+}
+
+fn track_last_hitter(
+    mut paddle_hits: EventReader<PaddleHitEvent>,
+    mut goals: EventReader<GoalEvent>,
+    mut last_hitter: ResMut<LastHitter>,
+) {
+    for hit in paddle_hits.iter() {
+        last_hitter.0 = Some(hit.player);
+    }
+    //A fresh serve hasn't been touched by anyone yet
+    if goals.iter().count() > 0 {
+        last_hitter.0 = None;
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn collect_power_ups(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    bounds: Res<CourtBounds>,
+    last_hitter: Res<LastHitter>,
+    pickups: Query<(Entity, &Transform, &PowerUp)>,
+    mut balls: Query<
+        (
+            Entity,
+            &Transform,
+            &mut Velocity,
+            &mut BallSpeed,
+            Option<&ActiveEffect>,
+        ),
+        With<Ball>,
+    >,
+    mut paddles: Query<(Entity, &Paddle, &PaddleLength, &mut PaddleSize), Without<Ball>>,
+    mut shields: Query<&mut Shield>,
+) {
+    //Nobody to reward until a paddle has touched the ball
+    let collector = match last_hitter.0 {
+        Some(player) => player,
+        None => return,
+    };
+    let reach = settings.ball.radius + settings.powerups.size;
+
+    for (pickup_entity, pickup_transform, &PowerUp(kind)) in pickups.iter() {
+        let pickup_pos = pickup_transform.translation.truncate();
+        let touching = balls.iter_mut().find(|(_, transform, velocity, ..)| {
+            //A ball waiting to be served can't collect anything
+            velocity.0 != Vec2::ZERO
+                && transform.translation.truncate().distance(pickup_pos) <= reach
+        });
+        let (ball_entity, _, mut velocity, mut speed, current) = match touching {
+            Some(ball) => ball,
+            None => continue,
+        };
+
+        commands.entity(pickup_entity).despawn();
+        //Picking up the same kind again just restarts the timer
+        let effect = ActiveEffect {
+            kind,
+            timer: Timer::from_seconds(settings.powerups.duration, false),
+        };
+
+        let target = match kind {
+            PowerUpKind::Shield => {
+                let existing = shields.iter_mut().find(|shield| shield.owner == collector);
+                match existing {
+                    Some(mut shield) => shield.timer = effect.timer,
+                    None => {
+                        let position = shield_position(&bounds, &settings, collector);
+                        commands
+                            .spawn_bundle((
+                                Transform::from_translation(position.extend(0.0)),
+                                GlobalTransform::default(),
+                            ))
+                            .insert(InGame)
+                            .insert(Shield {
+                                owner: collector,
+                                timer: effect.timer,
+                            });
+                    }
+                }
+                continue;
+            }
+            PowerUpKind::BigPaddle | PowerUpKind::StickyPaddle => collector,
+            PowerUpKind::NarrowOpponentPaddle => collector.opponent(),
+            PowerUpKind::SlowBall | PowerUpKind::FastBall => {
+                //Replaces whatever the ball had, so undo that first
+                let previous = current.map_or(1.0, |current| current.kind.ball_factor());
+                let factor = kind.ball_factor() / previous;
+                velocity.0 *= factor;
+                speed.0 *= factor;
+                commands.entity(ball_entity).insert(effect);
+                continue;
+            }
+        };
+
+        //Paddle effects are relative to the paddle's own length, so a new one just overrides
+        for (paddle_entity, paddle, length, mut size) in paddles.iter_mut() {
+            if paddle.0 == target {
+                size.0 = paddle_size(&settings.paddle, paddle.0, length.0 * kind.paddle_factor());
+                commands.entity(paddle_entity).insert(effect);
+                if kind == PowerUpKind::StickyPaddle {
+                    commands.entity(paddle_entity).insert(Sticky);
+                } else {
+                    commands.entity(paddle_entity).remove::<Sticky>();
+                }
+                break;
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn expire_effects(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    settings: Res<Settings>,
+    mut query: Query<(
+        Entity,
+        &mut ActiveEffect,
+        Option<(&Paddle, &PaddleLength, &mut PaddleSize)>,
+        Option<&mut Velocity>,
+        Option<&mut BallSpeed>,
+    )>,
+) {
+    for (entity, mut effect, paddle, velocity, speed) in query.iter_mut() {
+        if !effect.timer.tick(clock.delta()).finished() {
+            continue;
+        }
+
+        if let Some((paddle, length, mut size)) = paddle {
+            size.0 = paddle_size(&settings.paddle, paddle.0, length.0);
+        }
+        let factor = effect.kind.ball_factor();
+        if let (Some(mut velocity), Some(mut speed)) = (velocity, speed) {
+            velocity.0 /= factor;
+            speed.0 /= factor;
+        }
+        if effect.kind == PowerUpKind::StickyPaddle {
+            commands.entity(entity).remove::<Sticky>();
+        }
+        commands.entity(entity).remove::<ActiveEffect>();
+    }
+}
+
+fn update_shields(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    settings: Res<Settings>,
+    bounds: Res<CourtBounds>,
+    mut query: Query<(Entity, &mut Shield, &mut Transform)>,
+) {
+    for (entity, mut shield, mut transform) in query.iter_mut() {
+        if shield.timer.tick(clock.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        //Follows the court if the window is resized while it's up
+        let position = shield_position(&bounds, &settings, shield.owner);
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
+
+//Restarting a point takes away whatever the pickups gave, the balls carrying effects are already gone
+fn cancel_power_ups(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut restarts: EventReader<RestartEvent>,
+    mut last_hitter: ResMut<LastHitter>,
+    mut paddles: Query<(Entity, &Paddle, &PaddleLength, &mut PaddleSize), With<ActiveEffect>>,
+    shields: Query<Entity, With<Shield>>,
+) {
+    if restarts.iter().count() == 0 {
+        return;
+    }
+
+    last_hitter.0 = None;
+    for (entity, paddle, length, mut size) in paddles.iter_mut() {
+        size.0 = paddle_size(&settings.paddle, paddle.0, length.0);
+        commands
+            .entity(entity)
+            .remove::<ActiveEffect>()
+            .remove::<Sticky>();
+    }
+    for entity in shields.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+//Launches a held ball when its player serves, or once it's been held too long, aimed by where it
+//sits on the paddle and dragged along by the paddle's movement like a normal return
+#[allow(clippy::type_complexity)]
+fn release_stuck_balls(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    settings: Res<Settings>,
+    paddles: Query<(&Paddle, &PaddleSize, &Velocity), Without<Ball>>,
+    mut balls: Query<(Entity, &StuckTo, &mut StickHold, &mut Velocity, &BallSpeed), With<Ball>>,
+) {
+    for (ball, stuck, mut hold, mut velocity, speed) in balls.iter_mut() {
+        let timed_out = hold.timer.tick(clock.delta()).finished();
+        let (paddle, size, paddle_velocity) = match paddles.get(stuck.0) {
+            Ok(paddle) => paddle,
+            Err(_) => continue,
+        };
+        if !timed_out && !bindings.just_pressed(Action::serve_for(paddle.0), &keys) {
+            continue;
+        }
+
+        let axis = paddle.0.axis();
+        let hit_offset = (hold.offset / (size.0.dot(axis) / 2.0)).clamp(-1.0, 1.0);
+        let direction = physics::bounce_direction(hit_offset, -paddle.0.goal_normal(), axis);
+        let drag = axis * paddle_velocity.0.dot(axis) * settings.ball.spin;
+        let max_speed = settings.ball.max_speed.max(speed.0);
+        velocity.0 = (direction * speed.0 + drag).clamp_length_max(max_speed);
+        commands
+            .entity(ball)
+            .remove::<StuckTo>()
+            .remove::<StickHold>();
+    }
+}
+
+//Bounces the ball back exactly like the top and bottom walls do
+fn shield_collision(
+    settings: Res<Settings>,
+    bounds: Res<CourtBounds>,
+    mut hits: EventWriter<WallHitEvent>,
+    shields: Query<&Shield>,
+    mut balls: Query<(&mut Transform, &mut Velocity), With<Ball>>,
+) {
+    let radius = settings.ball.radius;
+    for shield in shields.iter() {
+        //Distances are measured along the direction from the shield into the court
+        let normal = -shield.owner.goal_normal();
+        let line = shield_position(&bounds, &settings, shield.owner).dot(normal);
+        let face = line + SHIELD_WIDTH / 2.0 + radius;
+
+        for (mut transform, mut velocity) in balls.iter_mut() {
+            //Only the court side of the shield blocks, a ball already behind it carries on
+            let distance = transform.translation.truncate().dot(normal);
+            if distance < face && distance >= line && velocity.0.dot(normal) < 0.0 {
+                transform.translation += (normal * (face - distance)).extend(0.0);
+                velocity.0 = physics::reflect(velocity.0, normal);
+                hits.send(WallHitEvent {
+                    side: WallSide::Shield(shield.owner),
+                });
+            }
+        }
+    }
+}
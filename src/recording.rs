@@ -0,0 +1,465 @@
+use crate::ai::AiDifficulty;
+use crate::gameplay::{GameMode, MultiBallConfig, Score, ServeRng};
+use crate::history;
+use crate::input::{Action, Inputs, KeyBindings};
+use crate::physics::{CourtBounds, Gravity, SimulationClock};
+use crate::settings::Settings;
+use crate::GameState;
+use bevy::app::AppExit;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+//Plugins
+pub struct RecordingPlugin;
+
+impl Plugin for RecordingPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        //build_app hands over the session as a Recorder or a Playback before any plugin is added
+        if app.world().contains_resource::<Recorder>() {
+            app.add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(start_recording.system().before("reset_power_ups")),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(record_frame.system().after("mouse_decoder")),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing).with_system(save_recording.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver).with_system(archive_recording.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::GameOver).with_system(watch_replay.system()),
+            )
+            .add_system_to_stage(CoreStage::Last, save_on_exit.system());
+        }
+
+        //A Playback can also show up later, when a finished match is watched again
+        app.add_startup_system(start_playback.system())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(seed_playback.system().before("reset_power_ups")),
+            )
+            .add_system_to_stage(
+                CoreStage::First,
+                advance_playback.system().after("advance_clock"),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                play_keys.system().after(InputSystem).before("pause_system"),
+            );
+    }
+}
+
+//Resources
+//What this run does with its matches
+pub enum Session {
+    Live,
+    Record(PathBuf),
+    Replay(Box<Recording>),
+}
+
+//Everything needed to play a match again: how it was set up, then what the players did on
+//every frame. The final score is kept to check the replay against
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Recording {
+    pub seed: u64,
+    pub mode: GameMode,
+    pub difficulty: AiDifficulty,
+    pub settings: Settings,
+    pub multiball: MultiBallConfig,
+    pub gravity: Vec2,
+    pub court: Vec2,
+    pub frames: Vec<RecordedFrame>,
+    pub score: Vec<u32>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct RecordedFrame {
+    //Physics steps the frame ran, the inputs hold for all of them
+    pub steps: u32,
+    pub inputs: Inputs,
+    //Held keys that change the match without going through the paddles
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<Action>,
+}
+
+pub struct Recorder {
+    //Where --record asked for a copy, every finished match goes to the replays directory anyway
+    path: Option<PathBuf>,
+    recording: Option<Recording>,
+    //The match that just ended, until it's archived
+    finished: Option<Recording>,
+    //The last match to reach the game over screen, ready to watch again
+    last: Option<Recording>,
+}
+
+//The recording being played, one frame per frame
+pub struct Playback {
+    recording: Recording,
+    next: usize,
+    //A replay from the command line closes the game when it's over, one watched from the game
+    //over screen goes back to it
+    exit_when_done: bool,
+}
+
+impl Session {
+    //Reads --record <file> or --replay <file>, anything else plays as normal
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let flag = match args.next() {
+            Some(flag) => flag,
+            None => return Ok(Session::Live),
+        };
+        let path = args
+            .next()
+            .map(PathBuf::from)
+            .ok_or_else(|| format!("{} needs a file", flag))?;
+        match flag.as_str() {
+            "--record" => Ok(Session::Record(path)),
+            "--replay" => {
+                Recording::load(&path).map(|recording| Session::Replay(Box::new(recording)))
+            }
+            _ => Err(format!(
+                "Unknown argument {}, expected --record <file> or --replay <file>",
+                flag
+            )),
+        }
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self, Session::Replay(_))
+    }
+}
+
+impl Recording {
+    //Keys replayed as they were pressed, the paddles come back through Inputs
+    pub const ACTIONS: [Action; 8] = [
+        Action::Serve,
+        Action::LeftServe,
+        Action::RightServe,
+        Action::TopServe,
+        Action::BottomServe,
+        Action::SlowMotion,
+        Action::Restart,
+        Action::FastForward,
+    ];
+    const REPLAY_DIR: &'static str = "replays";
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        std::fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| serde_json::from_str(&contents).map_err(|error| error.to_string()))
+            .map_err(|error| format!("Failed to load {}: {}", path.display(), error))
+    }
+
+    //A file per match in the replays directory, named for when it finished
+    pub fn archive_path() -> PathBuf {
+        let name = format!("{}.json", chrono::Local::now().format("%Y-%m-%d_%H-%M-%S"));
+        history::data_dir()
+            .map(|dir| dir.join(Recording::REPLAY_DIR))
+            .unwrap_or_else(|| PathBuf::from(Recording::REPLAY_DIR))
+            .join(name)
+    }
+
+    pub fn save(&self, path: &Path) {
+        let result = serde_json::to_string(self)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| {
+                if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+                }
+                std::fs::write(path, contents).map_err(|error| error.to_string())
+            });
+
+        if let Err(error) = result {
+            warn!("Failed to write {}: {}", path.display(), error);
+        }
+    }
+}
+
+impl Recorder {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Recorder {
+            path,
+            recording: None,
+            finished: None,
+            last: None,
+        }
+    }
+}
+
+impl Playback {
+    pub fn new(recording: Recording) -> Self {
+        Playback {
+            recording,
+            next: 0,
+            exit_when_done: true,
+        }
+    }
+
+    //Watching from the game over screen
+    pub fn rewatch(recording: Recording) -> Self {
+        Playback {
+            exit_when_done: false,
+            ..Playback::new(recording)
+        }
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    //The frame being played right now
+    pub fn frame(&self) -> Option<&RecordedFrame> {
+        self.next
+            .checked_sub(1)
+            .and_then(|index| self.recording.frames.get(index))
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next > self.recording.frames.len()
+    }
+}
+
+//Helpers
+fn final_score(score: &Score, mode: GameMode) -> Vec<u32> {
+    mode.players()
+        .iter()
+        .map(|player| score.points(*player))
+        .collect()
+}
+
+fn finish_recording(recorder: &mut Recorder, score: &Score) {
+    //Only the latest match is kept at --record's path, a rematch records over it
+    if let Some(mut recording) = recorder.recording.take() {
+        recording.score = final_score(score, recording.mode);
+        if let Some(path) = recorder.path.as_ref() {
+            recording.save(path);
+            info!(
+                "Recorded {} frames to {}",
+                recording.frames.len(),
+                path.display()
+            );
+        }
+        recorder.finished = Some(recording);
+    }
+}
+
+//Sets the match up exactly as it was recorded. A windowed replay resizes the court to its own
+//window, so it only matches in a window of the recorded size
+fn set_up_match(recording: &Recording, commands: &mut Commands) {
+    commands.insert_resource(recording.settings.clone());
+    commands.insert_resource(recording.mode);
+    commands.insert_resource(recording.difficulty);
+    commands.insert_resource(recording.multiball.clone());
+    commands.insert_resource(Gravity(recording.gravity));
+    commands.insert_resource(CourtBounds {
+        half_width: recording.court.x,
+        half_height: recording.court.y,
+    });
+}
+
+//Systems
+#[allow(clippy::too_many_arguments)]
+fn start_recording(
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    difficulty: Res<AiDifficulty>,
+    multiball: Res<MultiBallConfig>,
+    gravity: Res<Gravity>,
+    bounds: Res<CourtBounds>,
+    mut rng: ResMut<ServeRng>,
+    mut recorder: ResMut<Recorder>,
+    playback: Option<Res<Playback>>,
+) {
+    //Watching a match again doesn't record it over
+    if playback.is_some() {
+        return;
+    }
+
+    let seed = rand::random();
+    rng.0 = StdRng::seed_from_u64(seed);
+    recorder.recording = Some(Recording {
+        seed,
+        mode: *mode,
+        difficulty: *difficulty,
+        settings: settings.clone(),
+        multiball: multiball.clone(),
+        gravity: gravity.0,
+        court: bounds.half_size(),
+        frames: Vec::new(),
+        score: Vec::new(),
+    });
+}
+
+fn record_frame(
+    clock: Res<SimulationClock>,
+    inputs: Res<Inputs>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut recorder: ResMut<Recorder>,
+) {
+    if let Some(recording) = recorder.recording.as_mut() {
+        recording.frames.push(RecordedFrame {
+            steps: clock.steps,
+            inputs: *inputs,
+            actions: Recording::ACTIONS
+                .iter()
+                .copied()
+                .filter(|action| bindings.pressed(*action, &keys))
+                .collect(),
+        });
+    }
+}
+
+fn save_recording(score: Res<Score>, mut recorder: ResMut<Recorder>) {
+    finish_recording(&mut recorder, &score);
+}
+
+//Closing the window mid-match still keeps what was played
+fn save_on_exit(
+    mut exits: EventReader<AppExit>,
+    score: Res<Score>,
+    mut recorder: ResMut<Recorder>,
+) {
+    if exits.iter().next().is_some() {
+        finish_recording(&mut recorder, &score);
+    }
+}
+
+fn archive_recording(mut recorder: ResMut<Recorder>) {
+    if let Some(recording) = recorder.finished.take() {
+        recording.save(&Recording::archive_path());
+        recorder.last = Some(recording);
+    }
+}
+
+fn watch_replay(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    recorder: Res<Recorder>,
+    playback: Option<Res<Playback>>,
+) {
+    if !keys.just_pressed(KeyCode::V) || playback.is_some() {
+        return;
+    }
+
+    //The match starts over on the next frame, the same way a replay from the command line does
+    if let Some(recording) = recorder.last.as_ref() {
+        set_up_match(recording, &mut commands);
+        commands.insert_resource(Playback::rewatch(recording.clone()));
+    }
+}
+
+fn start_playback(mut commands: Commands, playback: Option<Res<Playback>>) {
+    if let Some(playback) = playback {
+        set_up_match(playback.recording(), &mut commands);
+    }
+}
+
+fn seed_playback(playback: Option<Res<Playback>>, mut rng: ResMut<ServeRng>) {
+    if let Some(playback) = playback {
+        rng.0 = StdRng::seed_from_u64(playback.recording().seed);
+    }
+}
+
+fn advance_playback(
+    mut commands: Commands,
+    playback: Option<ResMut<Playback>>,
+    mut clock: ResMut<SimulationClock>,
+    score: Res<Score>,
+    mut state: ResMut<State<GameState>>,
+    mut exits: EventWriter<AppExit>,
+) {
+    let mut playback = match playback {
+        Some(playback) => playback,
+        None => return,
+    };
+    if playback.finished() {
+        return;
+    }
+
+    //Straight into the match, in this frame's update so the first recorded frame plays in it
+    if playback.next == 0 {
+        state.set(GameState::Playing).ok();
+    }
+
+    //The recorded steps stand in for real time, so a headless replay runs as fast as it can
+    playback.next += 1;
+    clock.steps = playback.frame().map_or(0, |frame| frame.steps);
+
+    if playback.finished() {
+        let recording = playback.recording();
+        let replayed = final_score(&score, recording.mode);
+        if replayed == recording.score {
+            info!("Replay finished with the recorded score {:?}", replayed);
+        } else {
+            warn!(
+                "Replay finished at {:?}, but the recording ended at {:?}",
+                replayed, recording.score
+            );
+        }
+        if playback.exit_when_done {
+            exits.send(AppExit);
+        } else {
+            commands.remove_resource::<Playback>();
+        }
+    }
+}
+
+fn play_keys(
+    mut commands: Commands,
+    playback: Option<Res<Playback>>,
+    bindings: Res<KeyBindings>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut held: Local<Vec<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+    mut exits: EventWriter<AppExit>,
+) {
+    let playback = match playback {
+        Some(playback) => playback,
+        None => {
+            held.clear();
+            return;
+        }
+    };
+
+    //Leaving a replay watched from the game goes back to the menu
+    if keys.just_pressed(KeyCode::Escape) {
+        if playback.exit_when_done {
+            exits.send(AppExit);
+        } else {
+            commands.remove_resource::<Playback>();
+            state.set(GameState::MainMenu).ok();
+        }
+    }
+
+    //Whatever is really pressed is dropped for the recorded keys, pressed again on the frames
+    //they first went down so just_pressed comes out the same
+    let recorded: Vec<KeyCode> = playback.frame().map_or(Vec::new(), |frame| {
+        frame
+            .actions
+            .iter()
+            .map(|action| bindings.key(*action))
+            .collect()
+    });
+    let pressed: Vec<KeyCode> = keys.get_pressed().copied().collect();
+    for key in pressed {
+        keys.reset(key);
+    }
+    for key in recorded.iter().filter(|key| held.contains(key)) {
+        keys.press(*key);
+    }
+    //Clears just_pressed for the keys still down from last frame
+    keys.update();
+    for key in recorded.iter() {
+        keys.press(*key);
+    }
+    *held = recorded;
+}
@@ -0,0 +1,277 @@
+use crate::gameplay::{GoalEvent, InGame, Player, RestartEvent, ServeTimer};
+use crate::input::{Action, KeyBindings};
+use crate::physics::{Ball, Paddle, PaddleSize, Velocity, PHYSICS_STAGE, PHYSICS_STEP};
+use crate::GameState;
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+//Plugins
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(ReplayRecorder::default())
+            .insert_resource(GhostReplay::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(reset_replay.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(start_replay.system().after("goal_detection"))
+                    .with_system(clear_recording_on_restart.system().after("request_restart"))
+                    .with_system(end_replay_on_serve.system().after("restart_point"))
+                    .with_system(play_replay.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Paused)
+                    .with_system(replay_on_demand.system())
+                    .with_system(play_replay.system()),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Paused).with_system(end_replay.system()))
+            .add_system_set_to_stage(
+                PHYSICS_STAGE,
+                SystemSet::new()
+                    .with_run_criteria(in_match.system())
+                    .with_system(record_frame.system().after("movement")),
+            );
+    }
+}
+
+//Resources
+//Where everything was on every physics step of the rally so far
+#[derive(Default)]
+pub struct ReplayRecorder {
+    frames: VecDeque<ReplayFrame>,
+}
+
+//The end of the last point, played back as ghosts
+#[derive(Default)]
+pub struct GhostReplay {
+    frames: Vec<ReplayFrame>,
+    paddle_sizes: Vec<(Player, Vec2)>,
+    elapsed: f32,
+    playing: bool,
+}
+
+//Balls are keyed by entity, so one leaving mid-rally doesn't shift the others onto its ghost
+struct ReplayFrame {
+    balls: Vec<(Entity, Vec2)>,
+    paddles: Vec<(Player, Vec2)>,
+}
+
+impl ReplayRecorder {
+    //Long rallies only keep their last half minute
+    const MAX_SECONDS: f64 = 30.0;
+}
+
+impl GhostReplay {
+    //About as long as the countdown to the next serve
+    const SECONDS: f64 = 3.0;
+
+    fn start(&mut self) {
+        self.elapsed = 0.0;
+        self.playing = !self.frames.is_empty();
+    }
+}
+
+//Components
+//Stand-ins for the recorded pieces, nothing but a transform so physics never sees them
+pub enum Ghost {
+    Ball(Entity),
+    Paddle(Player, Vec2),
+}
+
+//Helpers
+fn stop_replay(
+    replay: &mut GhostReplay,
+    ghosts: &Query<Entity, With<Ghost>>,
+    commands: &mut Commands,
+) {
+    replay.playing = false;
+    for entity in ghosts.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn spawn_ghost(ghost: Ghost, commands: &mut Commands) {
+    //Under the live pieces and their trail, over the court markings
+    commands
+        .spawn_bundle((
+            Transform::from_xyz(0.0, 0.0, -0.5),
+            GlobalTransform::default(),
+        ))
+        .insert(InGame)
+        .insert(ghost);
+}
+
+//Systems
+//Only real matches are worth recording, not the menu's demo
+fn in_match(state: Res<State<GameState>>) -> ShouldRun {
+    if *state.current() == GameState::Playing {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn reset_replay(mut recorder: ResMut<ReplayRecorder>, mut replay: ResMut<GhostReplay>) {
+    recorder.frames.clear();
+    *replay = GhostReplay::default();
+}
+
+//A restarted point is replayed from the restart, not from the rally it threw away
+fn clear_recording_on_restart(
+    mut restarts: EventReader<RestartEvent>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    if restarts.iter().count() > 0 {
+        recorder.frames.clear();
+    }
+}
+
+fn record_frame(
+    mut recorder: ResMut<ReplayRecorder>,
+    balls: Query<(Entity, &Transform, &Velocity), With<Ball>>,
+    paddles: Query<(&Paddle, &Transform)>,
+) {
+    //Nothing happens while the balls wait to be served
+    if balls
+        .iter()
+        .all(|(_, _, velocity)| velocity.0 == Vec2::ZERO)
+    {
+        return;
+    }
+
+    recorder.frames.push_back(ReplayFrame {
+        balls: balls
+            .iter()
+            .map(|(ball, transform, _)| (ball, transform.translation.truncate()))
+            .collect(),
+        paddles: paddles
+            .iter()
+            .map(|(paddle, transform)| (paddle.0, transform.translation.truncate()))
+            .collect(),
+    });
+
+    let max_frames = (ReplayRecorder::MAX_SECONDS / PHYSICS_STEP) as usize;
+    while recorder.frames.len() > max_frames {
+        recorder.frames.pop_front();
+    }
+}
+
+fn start_replay(
+    mut commands: Commands,
+    mut goals: EventReader<GoalEvent>,
+    mut recorder: ResMut<ReplayRecorder>,
+    mut replay: ResMut<GhostReplay>,
+    paddles: Query<(&Paddle, &PaddleSize)>,
+    ghosts: Query<Entity, With<Ghost>>,
+) {
+    if goals.iter().count() == 0 {
+        return;
+    }
+
+    //Every point starts over with a clean recording
+    stop_replay(&mut replay, &ghosts, &mut commands);
+    let clip_frames = (GhostReplay::SECONDS / PHYSICS_STEP) as usize;
+    let skip = recorder.frames.len().saturating_sub(clip_frames);
+    replay.frames = recorder.frames.drain(..).skip(skip).collect();
+    replay.paddle_sizes = paddles
+        .iter()
+        .map(|(paddle, size)| (paddle.0, size.0))
+        .collect();
+    replay.start();
+}
+
+fn end_replay_on_serve(
+    mut commands: Commands,
+    serve_timer: Res<ServeTimer>,
+    mut replay: ResMut<GhostReplay>,
+    ghosts: Query<Entity, With<Ghost>>,
+) {
+    if replay.playing && serve_timer.timer.just_finished() {
+        stop_replay(&mut replay, &ghosts, &mut commands);
+    }
+}
+
+fn replay_on_demand(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut replay: ResMut<GhostReplay>,
+    ghosts: Query<Entity, With<Ghost>>,
+) {
+    //Starts the last point over from the beginning, even halfway through watching it
+    if bindings.just_pressed(Action::Replay, &keys) {
+        stop_replay(&mut replay, &ghosts, &mut commands);
+        replay.start();
+    }
+}
+
+fn end_replay(
+    mut commands: Commands,
+    mut replay: ResMut<GhostReplay>,
+    ghosts: Query<Entity, With<Ghost>>,
+) {
+    stop_replay(&mut replay, &ghosts, &mut commands);
+}
+
+fn play_replay(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut replay: ResMut<GhostReplay>,
+    mut ghosts: Query<(&Ghost, &mut Transform, Option<&mut Visible>)>,
+    ghost_entities: Query<Entity, With<Ghost>>,
+) {
+    if !replay.playing {
+        return;
+    }
+
+    //Ghosts appear on the first frame, more balls can join later in a multi-ball rally
+    if ghost_entities.iter().next().is_none() {
+        let mut balls = Vec::new();
+        for &(ball, _) in replay.frames.iter().flat_map(|frame| frame.balls.iter()) {
+            if !balls.contains(&ball) {
+                balls.push(ball);
+            }
+        }
+        for ball in balls {
+            spawn_ghost(Ghost::Ball(ball), &mut commands);
+        }
+        for &(player, size) in replay.paddle_sizes.iter() {
+            spawn_ghost(Ghost::Paddle(player, size), &mut commands);
+        }
+    }
+
+    let index = (replay.elapsed as f64 / PHYSICS_STEP) as usize;
+    replay.elapsed += time.delta_seconds();
+    let frame = match replay.frames.get(index) {
+        Some(frame) => frame,
+        None => {
+            stop_replay(&mut replay, &ghost_entities, &mut commands);
+            return;
+        }
+    };
+
+    for (ghost, mut transform, visible) in ghosts.iter_mut() {
+        let position = match ghost {
+            Ghost::Ball(ball) => frame
+                .balls
+                .iter()
+                .find(|(entity, _)| entity == ball)
+                .map(|(_, position)| *position),
+            Ghost::Paddle(player, _) => frame
+                .paddles
+                .iter()
+                .find(|(paddle, _)| paddle == player)
+                .map(|(_, position)| *position),
+        };
+        if let Some(position) = position {
+            transform.translation = position.extend(transform.translation.z);
+        }
+        if let Some(mut visible) = visible {
+            visible.is_visible = position.is_some();
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use crate::ai::AiConfig;
+use crate::audio::AudioConfig;
+use crate::gameplay::{HandicapConfig, MultiBallConfig, SavedHandicaps, ServeRule};
+use crate::graphics::{
+    ColorblindMode, ShakeConfig, Theme, ThemePreset, TrailConfig, VisualEffects, WindowConfig,
+};
+use crate::input::{ControlsConfig, GamepadConfig, KeyBindings};
+use crate::physics::{GravityConfig, ObstacleConfig};
+use crate::powerups::PowerUpConfig;
+use crate::ui::UiConfig;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+//Plugins
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let settings = Settings::load(&Settings::path());
+        let theme = settings.colors.theme();
+        let ui_config = UiConfig {
+            score_color: theme.ui_text,
+            ..UiConfig::default()
+        };
+
+        app.insert_resource(settings.window.descriptor())
+            .insert_resource(settings.window.court())
+            .insert_resource(ClearColor(theme.background))
+            .insert_resource(theme)
+            .insert_resource(ui_config)
+            .insert_resource(settings.keys.clone())
+            .insert_resource(settings.audio.clone())
+            .insert_resource(settings.effects.clone())
+            .insert_resource(ColorblindMode(settings.effects.colorblind))
+            .insert_resource(SavedHandicaps(settings.handicap))
+            .insert_resource(settings.multiball.clone())
+            .insert_resource(settings.gravity.gravity())
+            .insert_resource(settings.ai.difficulty)
+            .insert_resource(settings);
+    }
+}
+
+//Resources
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub paddle: PaddleConfig,
+    pub ball: BallConfig,
+    pub rules: MatchSettings,
+    pub colors: ColorSettings,
+    pub gamepad: GamepadConfig,
+    pub controls: ControlsConfig,
+    pub keys: KeyBindings,
+    pub audio: AudioConfig,
+    pub shake: ShakeConfig,
+    pub trail: TrailConfig,
+    pub effects: VisualEffects,
+    pub multiball: MultiBallConfig,
+    pub powerups: PowerUpConfig,
+    pub gravity: GravityConfig,
+    pub obstacle: ObstacleConfig,
+    pub ai: AiConfig,
+    pub window: WindowConfig,
+    pub handicap: HandicapConfig,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PaddleConfig {
+    pub speed: f32,
+    pub acceleration: f32,
+    pub friction: f32,
+    pub width: f32,
+    pub height: f32,
+    pub edge_offset: f32,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BallConfig {
+    pub radius: f32,
+    pub base_speed: f32,
+    pub speed_multiplier: f32,
+    pub max_speed: f32,
+    pub spin: f32,
+    //Radians per second of spin for every unit per second the paddle moves
+    pub spin_transfer: f32,
+    //How strongly spin curves the ball, and how quickly it wears off per second
+    pub spin_coefficient: f32,
+    pub spin_decay: f32,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MatchSettings {
+    //Points to win a set, the match goes to whoever takes sets_to_win sets first
+    pub points_to_win: u32,
+    pub sets_to_win: u32,
+    pub win_by_two: bool,
+    //Every goal conceded shortens that player's paddle
+    pub shrinking_paddles: bool,
+    pub serving: ServeRule,
+    //Slows the match down while the ball closes in on a player one goal from losing
+    pub match_point_slow_motion: bool,
+    //Minutes a match lasts, zero plays to points_to_win with no clock. A tie when time runs out
+    //goes to sudden death
+    pub time_limit: f32,
+}
+
+//Colors are stored as plain RGB triples so the file stays easy to edit
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ColorSettings {
+    //Any preset but Custom takes over from the colors below
+    pub theme: ThemePreset,
+    pub background: [f32; 3],
+    pub geometry: [f32; 3],
+    pub text: [f32; 3],
+}
+
+impl Settings {
+    const FILE_NAME: &'static str = "settings.toml";
+
+    pub fn path() -> PathBuf {
+        //Prefer the directory of the executable, but fall back to the working directory
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join(Settings::FILE_NAME)))
+            .unwrap_or_else(|| PathBuf::from(Settings::FILE_NAME))
+    }
+
+    pub fn load(path: &Path) -> Self {
+        //Missing or broken files fall back to the built in defaults
+        match std::fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|error| {
+                warn!("Failed to parse {}: {}", path.display(), error);
+                Settings::default()
+            }),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                //First run, leave a template behind for the user to edit
+                let settings = Settings::default();
+                settings.save(path);
+                settings
+            }
+            Err(error) => {
+                warn!("Failed to read {}: {}", path.display(), error);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) {
+        let result = toml::to_string(self)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| std::fs::write(path, contents).map_err(|error| error.to_string()));
+
+        if let Err(error) = result {
+            warn!("Failed to write {}: {}", path.display(), error);
+        }
+    }
+}
+
+impl MatchSettings {
+    //Win by two is measured against the closest challenger, table tennis always plays to deuce
+    pub fn is_won(&self, points: u32, challenger: u32) -> bool {
+        self.reaches(self.points_to_win, points, challenger)
+    }
+
+    pub fn reaches(&self, target: u32, points: u32, challenger: u32) -> bool {
+        let win_by_two = self.win_by_two || self.serving == ServeRule::Alternate;
+        let margin = if win_by_two { 2 } else { 1 };
+        points >= target && points >= challenger + margin
+    }
+}
+
+impl ColorSettings {
+    pub fn theme(&self) -> Theme {
+        self.theme.theme().unwrap_or_else(|| Theme {
+            background: self.background(),
+            ball: self.geometry(),
+            left_paddle: self.geometry(),
+            right_paddle: self.geometry(),
+            ui_text: self.text(),
+            court_markings: self.geometry(),
+        })
+    }
+
+    pub fn background(&self) -> Color {
+        rgb(self.background)
+    }
+
+    pub fn geometry(&self) -> Color {
+        rgb(self.geometry)
+    }
+
+    pub fn text(&self) -> Color {
+        rgb(self.text)
+    }
+}
+
+fn rgb([r, g, b]: [f32; 3]) -> Color {
+    Color::rgb(r, g, b)
+}
+
+impl Default for PaddleConfig {
+    fn default() -> Self {
+        PaddleConfig {
+            speed: 600.0,
+            acceleration: 5000.0,
+            friction: 4000.0,
+            width: 16.0,
+            height: 64.0,
+            edge_offset: 140.0,
+        }
+    }
+}
+
+impl Default for BallConfig {
+    fn default() -> Self {
+        BallConfig {
+            radius: 8.0,
+            base_speed: 100.0,
+            speed_multiplier: 1.05,
+            max_speed: 600.0,
+            spin: 0.3,
+            spin_transfer: 0.02,
+            spin_coefficient: 0.05,
+            spin_decay: 1.5,
+        }
+    }
+}
+
+impl Default for ColorSettings {
+    fn default() -> Self {
+        ColorSettings {
+            theme: ThemePreset::Custom,
+            background: [0.1, 0.1, 0.1],
+            geometry: [0.9, 0.9, 0.9],
+            text: [0.9, 0.9, 0.9],
+        }
+    }
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        MatchSettings {
+            points_to_win: 7,
+            sets_to_win: 1,
+            win_by_two: false,
+            shrinking_paddles: false,
+            serving: ServeRule::Loser,
+            match_point_slow_motion: false,
+            time_limit: 0.0,
+        }
+    }
+}
@@ -0,0 +1,1148 @@
+use crate::ai::AiDifficulty;
+use crate::gameplay::{
+    GameMode, InGame, MatchConfig, MatchStats, MatchTimer, Player, Rally, Score, ServeState,
+    ServeTimer, SetScore,
+};
+use crate::graphics::{alt_held, ColorblindMode, Theme, VisualEffects};
+use crate::history::{HighScore, MatchHistory};
+use crate::input::{Action, KeyBindings};
+use crate::physics::{Ball, Gravity, Paddle, SimulationClock, Velocity};
+use crate::settings::Settings;
+use crate::{despawn_with, GameState};
+use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::ecs::component::Component;
+use bevy::prelude::*;
+
+//Plugins
+pub struct UiPlugin;
+
+impl Plugin for UiPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_plugin(FrameTimeDiagnosticsPlugin::default())
+            .init_resource::<UiConfig>()
+            .insert_resource(RebindState::default())
+            .insert_resource(DebugOverlay(false))
+            .add_startup_system(setup_ui_camera.system())
+            .add_startup_system(spawn_debug_overlay.system())
+            //Outside every state so it stays up through menus and matches alike
+            .add_system(toggle_debug_overlay.system().label("toggle_debug_overlay"))
+            .add_system(update_debug_overlay.system().after("toggle_debug_overlay"))
+            .add_system_set(
+                SystemSet::on_enter(GameState::MainMenu).with_system(spawn_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::MainMenu).with_system(menu_input.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::MainMenu)
+                    .with_system(despawn_with::<MenuUi>.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Controls).with_system(reset_controls_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Controls)
+                    .with_system(controls_input.system().label("controls_input"))
+                    .with_system(update_controls_ui.system().after("controls_input")),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Controls)
+                    .with_system(despawn_with::<ControlsUi>.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::History).with_system(spawn_history.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::History).with_system(history_input.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::History)
+                    .with_system(despawn_with::<HistoryUi>.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Records).with_system(spawn_records.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Records).with_system(history_input.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Records)
+                    .with_system(despawn_with::<RecordsUi>.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::GameOver)
+                    .with_system(game_over_input.system().label("game_over_input"))
+                    .with_system(spawn_game_over.system().after("game_over_input")),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::GameOver)
+                    .with_system(despawn_with::<GameOverUi>.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Paused).with_system(spawn_pause.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Paused).with_system(despawn_with::<PauseUi>.system()),
+            )
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(spawn_hud.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(spawn_countdown.system().after("serve_ball"))
+                    .with_system(update_countdown_ui.system().after("serve_ball"))
+                    .with_system(update_score_ui.system().after("update_score"))
+                    .with_system(update_serve_indicator.system().after("reset_ball"))
+                    .with_system(update_rally_ui.system())
+                    .with_system(update_match_clock_ui.system()),
+            );
+    }
+}
+
+//Resources
+pub struct UiConfig {
+    pub score_font_size: f32,
+    pub score_color: Color,
+    pub score_margin: f32,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        UiConfig {
+            score_font_size: 64.0,
+            score_color: Color::rgb(0.9, 0.9, 0.9),
+            score_margin: 32.0,
+        }
+    }
+}
+
+//Whether the F3 overlay with frame timings and the ball's state is showing
+pub struct DebugOverlay(pub bool);
+
+//When the overlay last redrew and the worst frame since, it only redraws every INTERVAL seconds
+#[derive(Default)]
+struct DebugRefresh {
+    elapsed: f32,
+    most_steps: u32,
+}
+
+impl DebugRefresh {
+    const INTERVAL: f32 = 0.25;
+    //Four steps is a frame at 30 FPS
+    const MAX_SMOOTH_STEPS: u32 = 4;
+}
+
+#[derive(Default)]
+pub struct RebindState {
+    pub selected: usize,
+    pub waiting: bool,
+}
+
+//Components
+pub struct ScoreText(pub Player);
+pub enum RallyText {
+    Current,
+    Best,
+}
+pub struct Countdown(pub Timer);
+pub struct CountdownText;
+pub struct ServeIndicator;
+pub struct MatchClockText;
+pub struct MenuUi;
+pub struct GameOverUi;
+pub struct PauseUi;
+pub struct ControlsUi;
+pub struct HistoryUi;
+pub struct RecordsUi;
+pub struct DebugText;
+
+//Helpers
+fn spawn_score_text<T: Component>(
+    font: Handle<Font>,
+    config: &UiConfig,
+    marker: T,
+    slot: usize,
+    slots: usize,
+    materials: &mut Assets<ColorMaterial>,
+    commands: &mut Commands,
+) {
+    //Each score is centered in its own slice of the screen
+    let width = 100.0 / slots as f32;
+    let left = Val::Percent(width * slot as f32);
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left,
+                    top: Val::Px(config.score_margin),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(width), Val::Auto),
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .insert(InGame)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "0",
+                        TextStyle {
+                            font,
+                            font_size: config.score_font_size,
+                            color: config.score_color,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(marker);
+        });
+}
+
+fn spawn_countdown_text(
+    font: Handle<Font>,
+    color: Color,
+    materials: &mut Assets<ColorMaterial>,
+    commands: &mut Commands,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .insert(InGame)
+        .insert(Countdown(Timer::from_seconds(0.5, false)))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font,
+                            font_size: 96.0,
+                            color,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(CountdownText);
+        });
+}
+
+fn spawn_text_screen<T: Component>(
+    lines: &[(&str, f32)],
+    font: Handle<Font>,
+    color: Color,
+    background: Color,
+    marker: T,
+    materials: &mut Assets<ColorMaterial>,
+    commands: &mut Commands,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(background.into()),
+            ..Default::default()
+        })
+        .insert(marker)
+        .with_children(|parent| {
+            for (line, font_size) in lines {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        *line,
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: *font_size,
+                            color,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            }
+        });
+}
+
+//Just off the corner of the server's side, or the middle of their edge for top and bottom
+fn serve_indicator_position(server: Player, margin: f32) -> Rect<Val> {
+    let margin = Val::Px(margin);
+    match server {
+        Player::Left => Rect {
+            left: margin,
+            bottom: margin,
+            ..Default::default()
+        },
+        Player::Right => Rect {
+            right: margin,
+            bottom: margin,
+            ..Default::default()
+        },
+        Player::Top => Rect {
+            left: Val::Percent(47.0),
+            top: margin,
+            ..Default::default()
+        },
+        Player::Bottom => Rect {
+            left: Val::Percent(47.0),
+            bottom: margin,
+            ..Default::default()
+        },
+    }
+}
+
+//Systems
+fn spawn_debug_overlay(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    ui_config: Res<UiConfig>,
+) {
+    //Never despawned, it stays empty while the overlay is off
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(8.0),
+                    top: Val::Px(8.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/DejaVuSansMono-Bold.ttf"),
+                    font_size: 16.0,
+                    color: ui_config.score_color,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(DebugText);
+}
+
+fn toggle_debug_overlay(keys: Res<Input<KeyCode>>, mut overlay: ResMut<DebugOverlay>) {
+    if keys.just_pressed(KeyCode::F3) {
+        overlay.0 = !overlay.0;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_debug_overlay(
+    time: Res<Time>,
+    overlay: Res<DebugOverlay>,
+    diagnostics: Option<Res<Diagnostics>>,
+    clock: Res<SimulationClock>,
+    score: Res<Score>,
+    mode: Res<GameMode>,
+    mut refresh: Local<DebugRefresh>,
+    entities: Query<Entity>,
+    balls: Query<(&Transform, &Velocity), With<Ball>>,
+    paddles: Query<(&Paddle, &Velocity)>,
+    mut texts: Query<&mut Text, With<DebugText>>,
+) {
+    //Turned off, it clears its text once and then does nothing at all
+    if !overlay.0 {
+        if overlay.is_changed() {
+            for mut text in texts.iter_mut() {
+                text.sections[0].value.clear();
+            }
+        }
+        return;
+    }
+
+    //Numbers changing every frame can't be read, so it only refreshes four times a second
+    refresh.most_steps = refresh.most_steps.max(clock.steps);
+    refresh.elapsed += time.delta_seconds();
+    if refresh.elapsed < DebugRefresh::INTERVAL && !overlay.is_changed() {
+        return;
+    }
+    refresh.elapsed = 0.0;
+
+    let average = |id| {
+        diagnostics
+            .as_ref()
+            .and_then(|diagnostics| diagnostics.get(id))
+            .and_then(|diagnostic| diagnostic.average())
+            .unwrap_or(0.0)
+    };
+    let fps = average(FrameTimeDiagnosticsPlugin::FPS);
+    let frame_time = average(FrameTimeDiagnosticsPlugin::FRAME_TIME);
+    //Needing more steps than this in one frame means the simulation is lurching to catch up
+    let keeping_up = refresh.most_steps <= DebugRefresh::MAX_SMOOTH_STEPS;
+    let mut lines = vec![
+        format!("FPS {:.1}", fps),
+        format!("FRAME {:.1} MS", frame_time * 1000.0),
+        format!(
+            "SIM {} ({} STEPS/FRAME MAX)",
+            if keeping_up {
+                "KEEPING UP"
+            } else {
+                "FALLING BEHIND"
+            },
+            refresh.most_steps
+        ),
+        format!("ENTITIES {}", entities.iter().count()),
+    ];
+    refresh.most_steps = 0;
+
+    let points: Vec<String> = mode
+        .players()
+        .iter()
+        .map(|player| score.points(*player).to_string())
+        .collect();
+    lines.push(format!("SCORE {}", points.join(" - ")));
+    for (transform, velocity) in balls.iter() {
+        let direction = velocity.0.y.atan2(velocity.0.x).to_degrees();
+        lines.push(format!(
+            "BALL {:.1} AT {:.1} DEG",
+            velocity.0.length(),
+            direction
+        ));
+        lines.push(format!(
+            "BALL POS {:.0}, {:.0}",
+            transform.translation.x, transform.translation.y
+        ));
+    }
+    for (paddle, velocity) in paddles.iter() {
+        let speed = velocity.0.dot(paddle.0.axis());
+        lines.push(format!("{:?} PADDLE {:.1}", paddle.0, speed).to_uppercase());
+    }
+
+    let value = lines.join("\n");
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+fn setup_ui_camera(mut commands: Commands) {
+    commands.spawn_bundle(UiCameraBundle::default());
+}
+
+fn spawn_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    theme: Res<Theme>,
+    bindings: Res<KeyBindings>,
+) {
+    let key_name = |action| format!("{:?}", bindings.key(action));
+    let controls = format!(
+        "LEFT: {}/{}    RIGHT: {}/{}",
+        key_name(Action::LeftUp),
+        key_name(Action::LeftDown),
+        key_name(Action::RightUp),
+        key_name(Action::RightDown),
+    );
+
+    spawn_text_screen(
+        &[
+            ("BEVY PONG", 96.0),
+            ("PRESS ENTER TO PLAY", 48.0),
+            ("PRESS 1/2/3 OR U TO PLAY THE COMPUTER", 32.0),
+            ("EASY / MEDIUM / HARD / UNFAIR", 24.0),
+            (
+                "HOLD SHIFT TO PLAY ON THE RIGHT, 0 TO WATCH (F FAST FORWARDS)",
+                24.0,
+            ),
+            ("PRESS 4 FOR FOUR PLAYERS, P TO PRACTICE", 24.0),
+            (&controls, 24.0),
+            (
+                "PRESS C TO CHANGE CONTROLS, H FOR MATCH HISTORY, R FOR RECORDS",
+                24.0,
+            ),
+        ],
+        asset_server.load("fonts/DejaVuSansMono-Bold.ttf"),
+        theme.ui_text,
+        Color::NONE,
+        MenuUi,
+        &mut materials,
+        &mut commands,
+    );
+}
+
+fn menu_input(
+    keys: Res<Input<KeyCode>>,
+    mut mode: ResMut<GameMode>,
+    mut difficulty: ResMut<AiDifficulty>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if keys.just_pressed(KeyCode::Return) && !alt_held(&keys) {
+        *mode = GameMode::TwoPlayer;
+        state.set(GameState::Playing).ok();
+    }
+
+    if keys.just_pressed(KeyCode::Key4) {
+        *mode = GameMode::FourPlayer;
+        state.set(GameState::Playing).ok();
+    }
+
+    if keys.just_pressed(KeyCode::P) {
+        *mode = GameMode::Practice;
+        state.set(GameState::Playing).ok();
+    }
+
+    let choices = [
+        (KeyCode::Key1, AiDifficulty::Easy),
+        (KeyCode::Key2, AiDifficulty::Medium),
+        (KeyCode::Key3, AiDifficulty::Hard),
+        (KeyCode::U, AiDifficulty::Unfair),
+    ];
+    let shift = keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift);
+    for (key, choice) in choices {
+        if keys.just_pressed(key) {
+            *mode = if shift {
+                GameMode::VsAiRight
+            } else {
+                GameMode::VsAiLeft
+            };
+            *difficulty = choice;
+            state.set(GameState::Playing).ok();
+        }
+    }
+
+    //Both paddles at the last difficulty picked
+    if keys.just_pressed(KeyCode::Key0) {
+        *mode = GameMode::AiVsAi;
+        state.set(GameState::Playing).ok();
+    }
+
+    if keys.just_pressed(KeyCode::C) {
+        state.set(GameState::Controls).ok();
+    }
+
+    if keys.just_pressed(KeyCode::H) {
+        state.set(GameState::History).ok();
+    }
+
+    if keys.just_pressed(KeyCode::R) {
+        state.set(GameState::Records).ok();
+    }
+}
+
+fn reset_controls_menu(mut rebind: ResMut<RebindState>) {
+    *rebind = RebindState::default();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_controls_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    settings: Res<Settings>,
+    theme: Res<Theme>,
+    colorblind: Res<ColorblindMode>,
+    bindings: Res<KeyBindings>,
+    rebind: Res<RebindState>,
+    gravity: Res<Gravity>,
+    query: Query<Entity, With<ControlsUi>>,
+) {
+    let unchanged = !bindings.is_changed() && !rebind.is_changed() && !gravity.is_changed();
+    if unchanged && !theme.is_changed() && !colorblind.is_changed() {
+        return;
+    }
+
+    //The screen is small enough to rebuild from scratch on every change
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let rows: Vec<String> = Action::ALL
+        .iter()
+        .enumerate()
+        .map(|(index, action)| {
+            let key = if rebind.waiting && index == rebind.selected {
+                String::from("PRESS A KEY")
+            } else {
+                format!("{:?}", bindings.key(*action))
+            };
+            let cursor = if index == rebind.selected { ">" } else { " " };
+            format!("{} {:<12} {:>12}", cursor, action.label(), key)
+        })
+        .collect();
+
+    let gravity_row = if gravity.0 == Vec2::ZERO {
+        "G GRAVITY OFF"
+    } else {
+        "G GRAVITY ON"
+    };
+
+    //Smaller rows than the other screens, so every binding still fits in a 720 high window
+    const ROW_SIZE: f32 = 26.0;
+    let mut lines = vec![("CONTROLS", 64.0)];
+    lines.extend(rows.iter().map(|row| (row.as_str(), ROW_SIZE)));
+    lines.push((gravity_row, ROW_SIZE));
+    let theme_row = format!("T THEME {}", settings.colors.theme.label());
+    lines.push((&theme_row, ROW_SIZE));
+    let colorblind_row = if colorblind.0 {
+        "B COLORBLIND SHAPES ON"
+    } else {
+        "B COLORBLIND SHAPES OFF"
+    };
+    lines.push((colorblind_row, ROW_SIZE));
+    lines.push((
+        "UP/DOWN SELECT  ENTER REBIND  A ARROW KEYS  BACKSPACE BACK",
+        24.0,
+    ));
+
+    spawn_text_screen(
+        &lines,
+        asset_server.load("fonts/DejaVuSansMono-Bold.ttf"),
+        theme.ui_text,
+        Color::NONE,
+        ControlsUi,
+        &mut materials,
+        &mut commands,
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn controls_input(
+    keys: Res<Input<KeyCode>>,
+    mut bindings: ResMut<KeyBindings>,
+    mut rebind: ResMut<RebindState>,
+    mut settings: ResMut<Settings>,
+    mut gravity: ResMut<Gravity>,
+    mut theme: ResMut<Theme>,
+    mut ui_config: ResMut<UiConfig>,
+    mut colorblind: ResMut<ColorblindMode>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if rebind.waiting {
+        if let Some(key) = keys.get_just_pressed().next() {
+            bindings.rebind(Action::ALL[rebind.selected], *key);
+            rebind.waiting = false;
+        }
+        return;
+    }
+
+    let count = Action::ALL.len();
+    if keys.just_pressed(KeyCode::Up) {
+        rebind.selected = (rebind.selected + count - 1) % count;
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        rebind.selected = (rebind.selected + 1) % count;
+    }
+    if keys.just_pressed(KeyCode::Return) && !alt_held(&keys) {
+        rebind.waiting = true;
+    }
+    //Takes effect from the next serve, and is saved along with the keys
+    if keys.just_pressed(KeyCode::G) {
+        settings.gravity.enabled = !settings.gravity.enabled;
+        *gravity = settings.gravity.gravity();
+    }
+    //Recolors everything on screen straight away, the demo behind the menu included
+    if keys.just_pressed(KeyCode::T) {
+        settings.colors.theme = settings.colors.theme.next();
+        *theme = settings.colors.theme();
+        ui_config.score_color = theme.ui_text;
+    }
+    //Paddles and scores switch shapes straight away
+    if keys.just_pressed(KeyCode::B) {
+        settings.effects.colorblind = !settings.effects.colorblind;
+        colorblind.0 = settings.effects.colorblind;
+    }
+
+    //Swaps every binding for the arrow key layout, saved like any other change
+    if keys.just_pressed(KeyCode::A) {
+        *bindings = KeyBindings::arrows();
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        settings.keys = bindings.clone();
+        settings.save(&Settings::path());
+        state.set(GameState::MainMenu).ok();
+    }
+}
+
+fn spawn_history(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    theme: Res<Theme>,
+    history: Res<MatchHistory>,
+) {
+    const SHOWN: usize = 10;
+
+    //Newest first, older matches stay in the file but don't fit on screen
+    let rows: Vec<String> = history
+        .matches
+        .iter()
+        .rev()
+        .take(SHOWN)
+        .map(|record| {
+            let points: Vec<String> = record.score.iter().map(u32::to_string).collect();
+            format!(
+                "{}  {:<9} FIRST TO {:<3} RALLY {}",
+                record.date,
+                points.join(" - "),
+                record.points_to_win,
+                record.longest_rally
+            )
+        })
+        .collect();
+
+    let mut lines = vec![("HISTORY", 64.0)];
+    if rows.is_empty() {
+        lines.push(("NO MATCHES PLAYED YET", 32.0));
+    }
+    lines.extend(rows.iter().map(|row| (row.as_str(), 24.0)));
+    lines.push(("BACKSPACE BACK", 24.0));
+
+    spawn_text_screen(
+        &lines,
+        asset_server.load("fonts/DejaVuSansMono-Bold.ttf"),
+        theme.ui_text,
+        Color::NONE,
+        HistoryUi,
+        &mut materials,
+        &mut commands,
+    );
+}
+
+fn spawn_records(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    theme: Res<Theme>,
+    high_score: Res<HighScore>,
+) {
+    let rows = [
+        format!("LONGEST RALLY   {}", high_score.longest_rally),
+        format!("HIGHEST SCORE   {}", high_score.highest_score),
+        format!("MOST GOALS      {}", high_score.most_goals),
+        format!("LONGEST POINT   {}S", high_score.longest_volley),
+    ];
+
+    let mut lines = vec![("RECORDS", 64.0)];
+    lines.extend(rows.iter().map(|row| (row.as_str(), 32.0)));
+    lines.push(("BACKSPACE BACK", 24.0));
+
+    spawn_text_screen(
+        &lines,
+        asset_server.load("fonts/DejaVuSansMono-Bold.ttf"),
+        theme.ui_text,
+        Color::NONE,
+        RecordsUi,
+        &mut materials,
+        &mut commands,
+    );
+}
+
+//Also leaves the records screen
+fn history_input(keys: Res<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+    if keys.just_pressed(KeyCode::Back) {
+        state.set(GameState::MainMenu).ok();
+    }
+}
+
+//Rebuilt whenever the handicaps for the rematch change
+#[allow(clippy::too_many_arguments)]
+fn spawn_game_over(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    theme: Res<Theme>,
+    settings: Res<Settings>,
+    score: Res<Score>,
+    mode: Res<GameMode>,
+    stats: Res<MatchStats>,
+    match_config: Res<MatchConfig>,
+    set_score: Res<SetScore>,
+    query: Query<Entity, With<GameOverUi>>,
+) {
+    if query.iter().next().is_some() && !settings.is_changed() {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let players = mode.players();
+    let winner = match_config.winner(*mode, &score, &set_score);
+    let winner = format!("{:?} player wins!", winner);
+    let points: Vec<String> = players
+        .iter()
+        .map(|player| score.points(*player).to_string())
+        .collect();
+    let final_score = format!("Final score {}", points.join(" - "));
+    //Every set's score in the order they were played, after the tally
+    let sets = if match_config.has_sets(*mode) {
+        let breakdown: Vec<String> = set_score
+            .sets
+            .iter()
+            .map(|(left, right)| format!("{}-{}", left, right))
+            .collect();
+        format!(
+            "Sets {} - {}   {}",
+            set_score.left_sets,
+            set_score.right_sets,
+            breakdown.join("  ")
+        )
+    } else {
+        String::new()
+    };
+    let longest_rally = format!("Longest rally {}", stats.longest_rally);
+    let paddle_hits = format!("Paddle hits {}", stats.paddle_hits);
+    let peak_speed = format!("Peak ball speed {:.0}", stats.peak_speed);
+    let handicaps = [Player::Left, Player::Right]
+        .iter()
+        .map(|player| {
+            let handicap = settings.handicap.get(*player);
+            format!(
+                "{:?} speed x{:.1} size x{:.1}",
+                player, handicap.speed, handicap.height
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("   ");
+    let mut lines = vec![(winner.as_str(), 64.0), (final_score.as_str(), 32.0)];
+    if !sets.is_empty() {
+        lines.push((&sets, 32.0));
+    }
+    lines.extend_from_slice(&[
+        (&longest_rally, 24.0),
+        (&paddle_hits, 24.0),
+        (&peak_speed, 24.0),
+        ("Press R for a rematch", 32.0),
+        (&handicaps, 24.0),
+        ("[ ] and - = change the left and right handicaps", 24.0),
+        ("Press V to watch the replay", 24.0),
+        ("Press escape to return to the menu", 24.0),
+    ]);
+    spawn_text_screen(
+        &lines,
+        asset_server.load("fonts/DejaVuSansMono-Bold.ttf"),
+        theme.ui_text,
+        Color::NONE,
+        GameOverUi,
+        &mut materials,
+        &mut commands,
+    );
+}
+
+fn game_over_input(
+    keys: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut state: ResMut<State<GameState>>,
+) {
+    //Kept for every rematch, the menu puts back the ones from the settings file
+    let adjustments = [
+        (KeyCode::LBracket, Player::Left, -1.0),
+        (KeyCode::RBracket, Player::Left, 1.0),
+        (KeyCode::Minus, Player::Right, -1.0),
+        (KeyCode::Equals, Player::Right, 1.0),
+    ];
+    for (key, player, steps) in adjustments {
+        if !keys.just_pressed(key) {
+            continue;
+        }
+        if let Some(handicap) = settings.handicap.get_mut(player) {
+            handicap.adjust(steps);
+        }
+    }
+
+    if keys.just_pressed(KeyCode::R) {
+        state.set(GameState::Playing).ok();
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        state.set(GameState::MainMenu).ok();
+    }
+}
+
+fn spawn_pause(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    theme: Res<Theme>,
+    bindings: Res<KeyBindings>,
+) {
+    let hint = format!("Press {:?} to resume", bindings.pause);
+    let replay = format!("Press {:?} to replay the last point", bindings.replay);
+    spawn_text_screen(
+        &[("PAUSED", 64.0), (&hint, 24.0), (&replay, 24.0)],
+        asset_server.load("fonts/DejaVuSansMono-Bold.ttf"),
+        theme.ui_text,
+        Color::rgba(0.0, 0.0, 0.0, 0.5),
+        PauseUi,
+        &mut materials,
+        &mut commands,
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_hud(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    ui_config: Res<UiConfig>,
+    settings: Res<Settings>,
+    effects: Res<VisualEffects>,
+    mode: Res<GameMode>,
+    serve_state: Res<ServeState>,
+) {
+    let font = asset_server.load("fonts/DejaVuSansMono-Bold.ttf");
+
+    //Practice has nobody to serve to, the wall never misses
+    if *mode != GameMode::Practice {
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: serve_indicator_position(serve_state.server, ui_config.score_margin),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    "SERVE",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 24.0,
+                        color: ui_config.score_color,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            })
+            .insert(InGame)
+            .insert(ServeIndicator);
+    }
+
+    //A reminder in the corner of each handicapped side, above where the serve marker goes
+    for &player in mode.players() {
+        let handicap = settings.handicap.get(player);
+        if !handicap.is_active() {
+            continue;
+        }
+        let mut position = serve_indicator_position(player, ui_config.score_margin);
+        position.bottom = Val::Px(ui_config.score_margin + 32.0);
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position,
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    format!("HANDICAP x{:.1} x{:.1}", handicap.speed, handicap.height),
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 16.0,
+                        color: ui_config.score_color,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            })
+            .insert(InGame);
+    }
+
+    //Timed matches count down along the bottom of the screen
+    if settings.rules.time_limit > 0.0 && *mode != GameMode::Practice {
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        bottom: Val::Px(ui_config.score_margin),
+                        ..Default::default()
+                    },
+                    size: Size::new(Val::Percent(100.0), Val::Auto),
+                    justify_content: JustifyContent::Center,
+                    ..Default::default()
+                },
+                material: materials.add(Color::NONE.into()),
+                ..Default::default()
+            })
+            .insert(InGame)
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            "",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 32.0,
+                                color: ui_config.score_color,
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(MatchClockText);
+            });
+    }
+
+    //Practice has no score to draw on the court, just the rally and the best one so far
+    if *mode == GameMode::Practice {
+        let markers = vec![RallyText::Current, RallyText::Best];
+        for (slot, marker) in markers.into_iter().enumerate() {
+            spawn_score_text(
+                font.clone(),
+                &ui_config,
+                marker,
+                slot,
+                2,
+                &mut materials,
+                &mut commands,
+            );
+        }
+        return;
+    }
+
+    //The graphics plugin draws the score on the court itself
+    if effects.court_score {
+        return;
+    }
+
+    let players = mode.players();
+    for (slot, player) in players.iter().enumerate() {
+        spawn_score_text(
+            font.clone(),
+            &ui_config,
+            ScoreText(*player),
+            slot,
+            players.len(),
+            &mut materials,
+            &mut commands,
+        );
+    }
+}
+
+fn spawn_countdown(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    theme: Res<Theme>,
+    serve_timer: Res<ServeTimer>,
+    query: Query<(), With<Countdown>>,
+) {
+    //Each serve gets a fresh countdown, the previous one despawns itself after "GO!"
+    if serve_timer.timer.finished() || query.iter().next().is_some() {
+        return;
+    }
+
+    spawn_countdown_text(
+        asset_server.load("fonts/DejaVuSansMono-Bold.ttf"),
+        theme.ui_text,
+        &mut materials,
+        &mut commands,
+    );
+}
+
+fn update_countdown_ui(
+    mut commands: Commands,
+    time: Res<Time>,
+    serve_timer: Res<ServeTimer>,
+    mut countdowns: Query<(Entity, &mut Countdown)>,
+    mut texts: Query<&mut Text, With<CountdownText>>,
+) {
+    let timer = &serve_timer.timer;
+    let value = if timer.finished() {
+        String::from("GO!")
+    } else {
+        let remaining = timer.duration().as_secs_f32() - timer.elapsed_secs();
+        format!("{}", remaining.ceil())
+    };
+
+    //The ball is served the moment the timer finishes, "GO!" lingers briefly after that
+    for (entity, mut countdown) in countdowns.iter_mut() {
+        if !timer.finished() {
+            countdown.0.reset();
+        } else if countdown.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+fn update_serve_indicator(
+    ui_config: Res<UiConfig>,
+    serve_state: Res<ServeState>,
+    mut query: Query<&mut Style, With<ServeIndicator>>,
+) {
+    if !serve_state.is_changed() {
+        return;
+    }
+
+    for mut style in query.iter_mut() {
+        style.position = serve_indicator_position(serve_state.server, ui_config.score_margin);
+    }
+}
+
+fn update_rally_ui(rally: Res<Rally>, mut query: Query<(&RallyText, &mut Text)>) {
+    //Checking for new text too, the HUD spawns after the rally last changed
+    for (rally_text, mut text) in query.iter_mut() {
+        let value = match rally_text {
+            RallyText::Current => format!("RALLY {}", rally.current),
+            RallyText::Best => format!("BEST {}", rally.best),
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+fn update_match_clock_ui(
+    match_timer: Res<MatchTimer>,
+    mut query: Query<&mut Text, With<MatchClockText>>,
+) {
+    let value = match match_timer.remaining() {
+        _ if match_timer.sudden_death => String::from("SUDDEN DEATH"),
+        //Rounded up, so the clock only reads 0:00 once time is actually up
+        Some(remaining) => {
+            let seconds = remaining.as_secs_f32().ceil() as u32;
+            format!("{}:{:02}", seconds / 60, seconds % 60)
+        }
+        None => return,
+    };
+
+    for mut text in query.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+fn update_score_ui(
+    score: Res<Score>,
+    colorblind: Res<ColorblindMode>,
+    mut query: Query<(&ScoreText, &mut Text)>,
+) {
+    if !score.is_changed() && !colorblind.is_changed() {
+        return;
+    }
+
+    for (score_text, mut text) in query.iter_mut() {
+        let points = score.points(score_text.0);
+        text.sections[0].value = if colorblind.0 {
+            format!("{}:{}", score_text.0.initial(), points)
+        } else {
+            points.to_string()
+        };
+    }
+}
@@ -0,0 +1,153 @@
+use bevy::app::{Events, ManualEventReader};
+use bevy::prelude::*;
+use bevy_pong::ai::{predict_crossing, AiDifficulty};
+use bevy_pong::gameplay::{GameMode, Player, ServeRng};
+use bevy_pong::physics::{Ball, BallSpeed, CourtBounds, Paddle, PaddleHitEvent, Velocity};
+use bevy_pong::settings::Settings;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+mod common;
+
+//Two seconds of game time, long enough for the ball to reach the AI's side
+const STEPS: usize = 240;
+
+//Fires a fast ball at the computer on the right, one wall bounce away from its paddle
+fn returns_fast_diagonal(difficulty: AiDifficulty) -> bool {
+    let mut builder = common::headless();
+    builder.insert_resource(ServeRng(StdRng::seed_from_u64(7)));
+    common::fixed_steps(&mut builder, 1);
+    let mut app = builder.app;
+    *app.world.get_resource_mut::<GameMode>().unwrap() = GameMode::VsAiLeft;
+    *app.world.get_resource_mut::<AiDifficulty>().unwrap() = difficulty;
+    common::start_match(&mut app);
+
+    //Skip the countdown so the serve never overrides the shot
+    common::skip_serve(&mut app);
+
+    let mut balls = app
+        .world
+        .query_filtered::<(&mut Transform, &mut Velocity, &mut BallSpeed), With<Ball>>();
+    for (mut transform, mut velocity, mut speed) in balls.iter_mut(&mut app.world) {
+        transform.translation = Vec3::new(-300.0, 0.0, transform.translation.z);
+        velocity.0 = Vec2::new(450.0, -450.0);
+        speed.0 = velocity.0.length();
+    }
+
+    let mut reader = ManualEventReader::<PaddleHitEvent>::default();
+    for _ in 0..STEPS {
+        app.update();
+        let events = app.world.get_resource::<Events<PaddleHitEvent>>().unwrap();
+        if reader.iter(events).any(|hit| hit.player == Player::Right) {
+            return true;
+        }
+    }
+    false
+}
+
+#[test]
+fn easy_misses_what_hard_returns() {
+    assert!(!returns_fast_diagonal(AiDifficulty::Easy));
+    assert!(returns_fast_diagonal(AiDifficulty::Hard));
+    assert!(returns_fast_diagonal(AiDifficulty::Unfair));
+}
+
+#[test]
+fn speed_handicap_slows_the_ai_too() {
+    let mut builder = common::headless();
+    common::fixed_steps(&mut builder, 1);
+    let mut app = builder.app;
+    *app.world.get_resource_mut::<GameMode>().unwrap() = GameMode::VsAiLeft;
+    *app.world.get_resource_mut::<AiDifficulty>().unwrap() = AiDifficulty::Unfair;
+    common::start_match(&mut app);
+    common::skip_serve(&mut app);
+    let mut settings = app.world.get_resource_mut::<Settings>().unwrap();
+    settings.handicap.right.speed = 0.5;
+    let full_speed = settings.paddle.speed;
+
+    //Headed for the right paddle well above it, so it has to move at its top speed
+    let mut balls = app
+        .world
+        .query_filtered::<(&mut Transform, &mut Velocity), With<Ball>>();
+    for (mut transform, mut velocity) in balls.iter_mut(&mut app.world) {
+        transform.translation = Vec3::new(-300.0, 250.0, transform.translation.z);
+        velocity.0 = Vec2::new(200.0, 0.0);
+    }
+    for _ in 0..5 {
+        app.update();
+    }
+
+    let mut paddles = app.world.query::<(&Paddle, &Velocity)>();
+    let speed = paddles
+        .iter(&app.world)
+        .find(|(paddle, _)| paddle.0 == Player::Right)
+        .map(|(_, velocity)| velocity.0.length())
+        .unwrap();
+    assert!((speed - full_speed * 0.5).abs() < 0.01);
+}
+
+#[test]
+fn ai_watches_the_ball_coming_at_it() {
+    let mut builder = common::headless();
+    common::fixed_steps(&mut builder, 1);
+    let mut app = builder.app;
+    *app.world.get_resource_mut::<GameMode>().unwrap() = GameMode::VsAiLeft;
+    *app.world.get_resource_mut::<AiDifficulty>().unwrap() = AiDifficulty::Unfair;
+    common::start_match(&mut app);
+    common::skip_serve(&mut app);
+
+    //The served ball heads away from the computer right under its paddle, while a second one
+    //comes at it from high up on the other side
+    let mut balls = app
+        .world
+        .query_filtered::<(&mut Transform, &mut Velocity), With<Ball>>();
+    for (mut transform, mut velocity) in balls.iter_mut(&mut app.world) {
+        transform.translation = Vec3::new(400.0, -20.0, transform.translation.z);
+        velocity.0 = Vec2::new(-300.0, 0.0);
+    }
+    app.world.spawn().insert_bundle((
+        Ball,
+        Transform::from_xyz(-300.0, 250.0, 0.0),
+        GlobalTransform::default(),
+        Velocity(Vec2::new(200.0, 0.0)),
+        BallSpeed(200.0),
+    ));
+    for _ in 0..5 {
+        app.update();
+    }
+
+    let mut paddles = app.world.query::<(&Paddle, &Velocity)>();
+    let velocity = paddles
+        .iter(&app.world)
+        .find(|(paddle, _)| paddle.0 == Player::Right)
+        .map(|(_, velocity)| velocity.0)
+        .unwrap();
+    assert!(velocity.y > 0.0);
+}
+
+#[test]
+fn prediction_folds_off_the_walls() {
+    let bounds = CourtBounds::default();
+    let paddle = Vec2::new(500.0, 0.0);
+    //Straight across lands where the line does
+    let straight = predict_crossing(
+        Vec2::ZERO,
+        Vec2::new(100.0, 10.0),
+        Player::Right,
+        paddle,
+        &bounds,
+        10.0,
+    );
+    assert!((straight - 50.0).abs() < 0.01);
+
+    //Would pass 100 under the bottom wall's reach, so it bounces back 100 above it
+    let bounced = predict_crossing(
+        Vec2::new(0.0, -300.0),
+        Vec2::new(100.0, -30.0),
+        Player::Right,
+        paddle,
+        &bounds,
+        10.0,
+    );
+    assert!((bounced - -250.0).abs() < 0.01);
+}
@@ -0,0 +1,46 @@
+//Shared by the integration tests, not every file needs all of it
+#![allow(dead_code)]
+
+use bevy::prelude::*;
+use bevy_pong::gameplay::ServeTimer;
+use bevy_pong::physics::SimulationClock;
+use bevy_pong::recording::Session;
+use bevy_pong::GameState;
+
+pub fn headless() -> AppBuilder {
+    bevy_pong::build_app(true, Session::Live)
+}
+
+//The same number of physics steps every frame whatever the real frame time
+pub fn fixed_steps(builder: &mut AppBuilder, steps: u32) {
+    builder.add_system_to_stage(
+        CoreStage::First,
+        (move |mut clock: ResMut<SimulationClock>| clock.steps = steps)
+            .system()
+            .after("advance_clock"),
+    );
+}
+
+//Straight into a match, with whatever was set on the world so far
+pub fn start_match(app: &mut App) {
+    app.world
+        .get_resource_mut::<State<GameState>>()
+        .unwrap()
+        .set(GameState::Playing)
+        .unwrap();
+    app.update();
+}
+
+//Finishes the countdown so the ball is in play from the next frame
+pub fn skip_serve(app: &mut App) {
+    let mut serve_timer = app.world.get_resource_mut::<ServeTimer>().unwrap();
+    let duration = serve_timer.timer.duration();
+    serve_timer.timer.tick(duration);
+}
+
+pub fn state(app: &App) -> GameState {
+    *app.world
+        .get_resource::<State<GameState>>()
+        .unwrap()
+        .current()
+}
@@ -0,0 +1,124 @@
+use bevy::app::{Events, ManualEventReader};
+use bevy::prelude::*;
+use bevy_pong::gameplay::{
+    goal_detection, shrink_paddles, update_score, GameMode, GoalEvent, LastTouch, Player, Score,
+};
+use bevy_pong::physics::{Ball, CourtBounds, Paddle, PaddleLength, PaddleSize, Velocity};
+use bevy_pong::settings::Settings;
+
+//Just the goal systems on a bare world with the default court, no app or plugins
+fn goal_world() -> World {
+    let mut world = World::default();
+    world.insert_resource(Settings::default());
+    world.insert_resource(CourtBounds::default());
+    world.insert_resource(GameMode::TwoPlayer);
+    world.insert_resource(Score::default());
+    world.insert_resource(Events::<GoalEvent>::default());
+    world
+}
+
+fn spawn_ball(world: &mut World, position: Vec2, velocity: Vec2) -> Entity {
+    world
+        .spawn()
+        .insert_bundle((
+            Ball,
+            Transform::from_translation(position.extend(0.0)),
+            Velocity(velocity),
+            LastTouch(None),
+        ))
+        .id()
+}
+
+fn goals(world: &World) -> Vec<(Player, Player, Vec2, Vec2)> {
+    let events = world.get_resource::<Events<GoalEvent>>().unwrap();
+    ManualEventReader::<GoalEvent>::default()
+        .iter(events)
+        .map(|goal| {
+            let exit = Vec2::new(goal.ball_exit_x, goal.ball_exit_y);
+            (goal.scorer, goal.conceder, exit, goal.ball_exit_velocity)
+        })
+        .collect()
+}
+
+#[test]
+fn ball_past_the_right_goal_scores_for_the_left() {
+    let mut world = goal_world();
+    let velocity = Vec2::new(300.0, -40.0);
+    spawn_ball(&mut world, Vec2::new(660.0, 25.0), velocity);
+
+    let mut stage = SystemStage::single(goal_detection.system());
+    stage.run(&mut world);
+    assert_eq!(
+        goals(&world),
+        vec![(
+            Player::Left,
+            Player::Right,
+            Vec2::new(660.0, 25.0),
+            velocity
+        )]
+    );
+
+    //Scoring only needs the event, not the ball that caused it
+    let mut stage = SystemStage::single(update_score.system());
+    stage.run(&mut world);
+    let score = world.get_resource::<Score>().unwrap();
+    assert_eq!((score.left, score.right), (1, 0));
+}
+
+#[test]
+fn ball_still_in_play_sends_nothing() {
+    let mut world = goal_world();
+    spawn_ball(&mut world, Vec2::new(600.0, 0.0), Vec2::new(300.0, 0.0));
+
+    let mut stage = SystemStage::single(goal_detection.system());
+    stage.run(&mut world);
+    assert!(goals(&world).is_empty());
+}
+
+#[test]
+fn conceding_shrinks_only_that_paddle_down_to_the_floor() {
+    let mut world = goal_world();
+    world
+        .get_resource_mut::<Settings>()
+        .unwrap()
+        .rules
+        .shrinking_paddles = true;
+    let spawn_paddle = |world: &mut World, player| {
+        world
+            .spawn()
+            .insert_bundle((
+                Paddle(player),
+                PaddleLength(64.0),
+                PaddleSize(Vec2::new(16.0, 64.0)),
+            ))
+            .id()
+    };
+    let left = spawn_paddle(&mut world, Player::Left);
+    let right = spawn_paddle(&mut world, Player::Right);
+
+    let mut stage = SystemStage::single(shrink_paddles.system());
+    let mut lengths = Vec::new();
+    for _ in 0..9 {
+        let mut events = world.get_resource_mut::<Events<GoalEvent>>().unwrap();
+        events.send(GoalEvent {
+            scorer: Player::Left,
+            conceder: Player::Right,
+            ball_exit_x: 660.0,
+            ball_exit_y: 0.0,
+            ball_exit_velocity: Vec2::new(300.0, 0.0),
+        });
+        stage.run(&mut world);
+        lengths.push(world.get::<PaddleSize>(right).unwrap().0.y);
+    }
+
+    //6 off for every goal until it stops at 20
+    assert_eq!(
+        lengths,
+        vec![58.0, 52.0, 46.0, 40.0, 34.0, 28.0, 22.0, 20.0, 20.0]
+    );
+    assert_eq!(world.get::<PaddleLength>(right).unwrap().0, 20.0);
+    assert_eq!(
+        world.get::<PaddleSize>(left).unwrap().0,
+        Vec2::new(16.0, 64.0)
+    );
+}
@@ -0,0 +1,46 @@
+use bevy::prelude::*;
+use bevy_pong::gameplay::{Player, Score, ServeRng};
+use bevy_pong::input::{Inputs, PaddleInputs};
+use bevy_pong::physics::Paddle;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+mod common;
+
+//Both paddles held into opposite corners, after the decoders so nothing overrides it
+fn dodge(mut inputs: ResMut<Inputs>) {
+    inputs.left = PaddleInputs::new(1.0);
+    inputs.right = PaddleInputs::new(-1.0);
+}
+
+#[test]
+fn unreturned_serve_scores() {
+    let mut builder = common::headless();
+    builder
+        .insert_resource(ServeRng(StdRng::seed_from_u64(3)))
+        .add_system(
+            dodge
+                .system()
+                .after("playback_decoder")
+                .before("handle_inputs"),
+        );
+    //A fifteenth of a second of game time each frame, the serve countdown included
+    common::fixed_steps(&mut builder, 8);
+    let mut app = builder.app;
+    common::start_match(&mut app);
+
+    for _ in 0..300 {
+        app.update();
+    }
+    let score = app.world.get_resource::<Score>().unwrap();
+    assert!(score.left + score.right > 0);
+
+    //The paddles went where they were told
+    let mut paddles = app.world.query::<(&Paddle, &Transform)>();
+    for (paddle, transform) in paddles.iter(&app.world) {
+        match paddle.0 {
+            Player::Left => assert!(transform.translation.y > 0.0),
+            _ => assert!(transform.translation.y < 0.0),
+        }
+    }
+}
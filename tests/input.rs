@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+use bevy_pong::gameplay::Player;
+use bevy_pong::input::{ConnectedGamepads, PaddleInputs};
+use bevy_pong::physics::{Paddle, Velocity};
+use bevy_pong::settings::Settings;
+
+mod common;
+
+//Half a second of holding the input, the paddle is at its top speed well before that
+fn left_paddle_speed(hold: impl FnOnce(&mut World)) -> f32 {
+    let mut builder = common::headless();
+    common::fixed_steps(&mut builder, 1);
+    let mut app = builder.app;
+    common::start_match(&mut app);
+    hold(&mut app.world);
+    for _ in 0..60 {
+        app.update();
+    }
+
+    let mut paddles = app.world.query::<(&Paddle, &Velocity)>();
+    let (_, velocity) = paddles
+        .iter(&app.world)
+        .find(|(paddle, _)| paddle.0 == Player::Left)
+        .unwrap();
+    velocity.0.y
+}
+
+#[test]
+fn buttons_map_to_the_ends_of_the_axis() {
+    assert_eq!(PaddleInputs::from_buttons(true, false).axis, 1.0);
+    assert_eq!(PaddleInputs::from_buttons(false, true).axis, -1.0);
+    assert_eq!(PaddleInputs::from_buttons(true, true).axis, 0.0);
+    assert_eq!(PaddleInputs::from_buttons(false, false).axis, 0.0);
+
+    //A key and a stick pushed the same way still can't beat full speed
+    let combined = PaddleInputs::from_buttons(true, false).combine(PaddleInputs::new(0.5));
+    assert_eq!(combined.axis, 1.0);
+}
+
+#[test]
+fn keyboard_moves_at_full_speed() {
+    let speed = left_paddle_speed(|world| {
+        let mut keys = world.get_resource_mut::<Input<KeyCode>>().unwrap();
+        keys.press(KeyCode::W);
+    });
+    assert_eq!(speed, Settings::default().paddle.speed);
+}
+
+#[test]
+fn half_tilted_stick_moves_at_half_speed() {
+    let speed = left_paddle_speed(|world| {
+        let gamepad = Gamepad(0);
+        world
+            .get_resource_mut::<ConnectedGamepads>()
+            .unwrap()
+            .connect(gamepad);
+        let mut axes = world.get_resource_mut::<Axis<GamepadAxis>>().unwrap();
+        axes.set(GamepadAxis(gamepad, GamepadAxisType::LeftStickY), 0.5);
+    });
+    assert_eq!(speed, Settings::default().paddle.speed / 2.0);
+}
+
+#[test]
+fn unplugging_a_pad_leaves_the_others_in_their_slots() {
+    let mut gamepads = ConnectedGamepads::default();
+    for id in 0..3 {
+        gamepads.connect(Gamepad(id));
+    }
+    gamepads.connect(Gamepad(1));
+
+    gamepads.disconnect(Gamepad(0));
+    assert_eq!(gamepads.0, [None, Some(Gamepad(1)), Some(Gamepad(2)), None]);
+
+    //The next pad in takes the paddle that was freed
+    gamepads.connect(Gamepad(5));
+    assert_eq!(
+        gamepads.0,
+        [Some(Gamepad(5)), Some(Gamepad(1)), Some(Gamepad(2)), None]
+    );
+}
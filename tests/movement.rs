@@ -0,0 +1,42 @@
+use bevy::prelude::*;
+use bevy_pong::gameplay::Player;
+use bevy_pong::physics::{Paddle, PaddleSize, SimulationClock, Velocity, PHYSICS_STEP};
+use bevy_pong::settings::Settings;
+use bevy_pong::{GameState, PhysicsPlugin};
+use std::time::Duration;
+
+#[test]
+fn movement_integrates_velocity_over_the_time_that_passed() {
+    //Just the physics, with Time only moving when the test says so
+    let mut builder = App::build();
+    builder
+        .insert_resource(Time::default())
+        .insert_resource(Settings::default())
+        .insert_resource(State::new(GameState::Playing))
+        .add_plugin(PhysicsPlugin);
+    let mut app = builder.app;
+    let paddle = app
+        .world
+        .spawn()
+        .insert_bundle((
+            Paddle(Player::Left),
+            PaddleSize(Vec2::new(16.0, 64.0)),
+            Transform::from_xyz(-500.0, 0.0, 0.0),
+            Velocity(Vec2::new(0.0, 240.0)),
+        ))
+        .id();
+
+    //The first update only starts the clock
+    app.world.get_resource_mut::<Time>().unwrap().update();
+    app.update();
+    std::thread::sleep(Duration::from_millis(100));
+    app.world.get_resource_mut::<Time>().unwrap().update();
+    app.update();
+
+    //Whole steps of the time that passed, the rest waits for the next frame
+    let steps = app.world.get_resource::<SimulationClock>().unwrap().steps;
+    assert!(steps >= 12);
+    let y = app.world.get::<Transform>(paddle).unwrap().translation.y;
+    let expected = 240.0 * steps as f32 * PHYSICS_STEP as f32;
+    assert!((y - expected).abs() < 0.01);
+}
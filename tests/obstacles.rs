@@ -0,0 +1,56 @@
+use bevy::prelude::*;
+use bevy_pong::gameplay::GameMode;
+use bevy_pong::physics::{Obstacle, ObstacleBlock};
+use bevy_pong::settings::Settings;
+
+mod common;
+
+//Starts a match with the given obstacle settings and returns where the obstacles ended up
+fn spawned_obstacles(mode: GameMode, configure: impl FnOnce(&mut Settings)) -> Vec<Vec2> {
+    let mut app = common::headless().app;
+    configure(&mut app.world.get_resource_mut::<Settings>().unwrap());
+    *app.world.get_resource_mut::<GameMode>().unwrap() = mode;
+    common::start_match(&mut app);
+
+    let mut query = app.world.query_filtered::<&Transform, With<Obstacle>>();
+    query
+        .iter(&app.world)
+        .map(|transform| transform.translation.truncate())
+        .collect()
+}
+
+#[test]
+fn classic_matches_have_no_obstacles() {
+    assert!(spawned_obstacles(GameMode::TwoPlayer, |_| {}).is_empty());
+}
+
+#[test]
+fn arcade_spawns_the_layout() {
+    let obstacles = spawned_obstacles(GameMode::TwoPlayer, |settings| {
+        settings.obstacle.arcade = true;
+    });
+    assert_eq!(obstacles.len(), Settings::default().obstacle.layout.len());
+}
+
+#[test]
+fn blocks_in_a_paddle_lane_are_left_out() {
+    //The default court puts the right paddle's lane at x = 500
+    let obstacles = spawned_obstacles(GameMode::TwoPlayer, |settings| {
+        settings.obstacle.arcade = true;
+        settings.obstacle.layout = vec![
+            ObstacleBlock {
+                x: 0.0,
+                y: 0.0,
+                width: 24.0,
+                height: 72.0,
+            },
+            ObstacleBlock {
+                x: 490.0,
+                y: 100.0,
+                width: 24.0,
+                height: 72.0,
+            },
+        ];
+    });
+    assert_eq!(obstacles, vec![Vec2::ZERO]);
+}
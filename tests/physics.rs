@@ -0,0 +1,321 @@
+use bevy::app::{Events, ManualEventReader};
+use bevy::prelude::*;
+use bevy_pong::gameplay::Player;
+use bevy_pong::physics::{
+    ball_box_contact, bounce_direction, detect_paddle_collisions, reflect, sweep_box, sweep_walls,
+    Ball, BallSpeed, CollisionEvent, CourtBounds, Paddle, PaddleSize, TimeScale, Velocity,
+    WallHitEvent, WallSide, MAX_BOUNCE_ANGLE, PHYSICS_STEP,
+};
+use bevy_pong::settings::Settings;
+
+mod common;
+
+const WALLS: [Player; 2] = [Player::Top, Player::Bottom];
+
+//A paddle at the default size grown by the default ball radius, as the paddle sweep sees it
+fn paddle_hit(start: Vec2, delta: Vec2) -> Option<(f32, Vec2)> {
+    sweep_box(start, delta, Vec2::new(500.0, 0.0), Vec2::new(16.0, 40.0))
+}
+
+//The default court less the ball's radius
+fn bounce(start: Vec2, velocity: Vec2, walls: &[Player]) -> (Vec2, Vec2, Vec<Player>) {
+    sweep_walls(start, velocity, 1.0, Vec2::new(632.0, 352.0), walls)
+}
+
+fn close(a: Vec2, b: Vec2) -> bool {
+    (a - b).length() < 0.001
+}
+
+#[test]
+fn face_hit_stops_at_the_face() {
+    let (time, normal) = paddle_hit(Vec2::new(400.0, 10.0), Vec2::new(168.0, 0.0)).unwrap();
+    assert!((time - 0.5).abs() < 0.001);
+    assert_eq!(normal, Vec2::new(-1.0, 0.0));
+}
+
+#[test]
+fn fast_ball_crossing_the_whole_paddle_still_hits() {
+    //Starts in front of the paddle and would end well behind it within one step
+    let start = Vec2::new(470.0, 0.0);
+    let delta = Vec2::new(100.0, 0.0);
+    assert!(start.x + delta.x > 500.0 + 16.0);
+
+    let (time, normal) = paddle_hit(start, delta).unwrap();
+    assert!(close(start + delta * time, Vec2::new(484.0, 0.0)));
+    assert_eq!(normal, Vec2::new(-1.0, 0.0));
+}
+
+#[test]
+fn grazing_the_end_of_the_paddle() {
+    //Skimming exactly along the end still touches it, a hair further out misses
+    let delta = Vec2::new(200.0, 0.0);
+    let edge = 40.0;
+    assert!(paddle_hit(Vec2::new(400.0, edge), delta).is_some());
+    assert!(paddle_hit(Vec2::new(400.0, edge + 0.01), delta).is_none());
+}
+
+#[test]
+fn corner_hit_lands_on_the_corner() {
+    //Aimed straight at the top left corner, it arrives at both faces at once
+    let corner = Vec2::new(484.0, 40.0);
+    let start = corner + Vec2::new(-50.0, 50.0);
+    let delta = Vec2::new(100.0, -100.0);
+
+    let (time, normal) = paddle_hit(start, delta).unwrap();
+    assert!((time - 0.5).abs() < 0.001);
+    assert!(close(start + delta * time, corner));
+    assert!(normal == Vec2::new(-1.0, 0.0) || normal == Vec2::new(0.0, 1.0));
+}
+
+#[test]
+fn moving_away_never_hits() {
+    let start = Vec2::new(400.0, 0.0);
+    assert!(paddle_hit(start, Vec2::new(-100.0, 0.0)).is_none());
+}
+
+#[test]
+fn overlapping_ball_is_pushed_out_the_shallow_side() {
+    //Already 6 deep into the paddle's front face and not moving into it any further
+    let start = Vec2::new(490.0, 0.0);
+    let (time, position, normal) = ball_box_contact(
+        start,
+        Vec2::ZERO,
+        Vec2::new(500.0, 0.0),
+        Vec2::new(16.0, 40.0),
+    )
+    .unwrap();
+    assert_eq!(time, 0.0);
+    assert!(close(position, Vec2::new(484.0, 0.0)));
+    assert_eq!(normal, Vec2::new(-1.0, 0.0));
+}
+
+#[test]
+fn detection_reports_the_ball_and_paddle_it_hits() {
+    //Just the detection system on a bare world, a step's travel of 100 reaches the paddle halfway
+    let mut world = World::default();
+    world.insert_resource(Settings::default());
+    world.insert_resource(TimeScale::default());
+    world.insert_resource(Events::<CollisionEvent>::default());
+    let step = 100.0 / PHYSICS_STEP as f32;
+    let ball = world
+        .spawn()
+        .insert_bundle((
+            Ball,
+            Transform::from_xyz(434.0, 0.0, 0.0),
+            Velocity(Vec2::new(step, 0.0)),
+        ))
+        .id();
+    world.spawn().insert_bundle((
+        Ball,
+        Transform::from_xyz(434.0, 200.0, 0.0),
+        Velocity(Vec2::new(-step, 0.0)),
+    ));
+    let paddle = world
+        .spawn()
+        .insert_bundle((
+            Transform::from_xyz(500.0, 0.0, 0.0),
+            PaddleSize(Vec2::new(16.0, 64.0)),
+        ))
+        .id();
+
+    let mut stage = SystemStage::single(detect_paddle_collisions.system());
+    stage.run(&mut world);
+
+    let events = world.get_resource::<Events<CollisionEvent>>().unwrap();
+    let collisions: Vec<CollisionEvent> =
+        ManualEventReader::default().iter(events).copied().collect();
+    assert_eq!(collisions.len(), 1);
+    assert_eq!(collisions[0].entity_a, ball);
+    assert_eq!(collisions[0].entity_b, paddle);
+    assert_eq!(collisions[0].normal, Vec2::new(-1.0, 0.0));
+    assert!((collisions[0].time - 0.5).abs() < 0.001);
+    assert!((collisions[0].closing_speed - step).abs() < 0.001);
+    assert!(collisions[0].is_impact());
+}
+
+#[test]
+fn pushing_out_a_ball_already_leaving_is_not_an_impact() {
+    let mut world = World::default();
+    world.insert_resource(Settings::default());
+    world.insert_resource(TimeScale::default());
+    world.insert_resource(Events::<CollisionEvent>::default());
+    //The paddle moved onto the ball's edge while it was heading back into the court
+    world.spawn().insert_bundle((
+        Ball,
+        Transform::from_xyz(486.0, 0.0, 0.0),
+        Velocity(Vec2::new(-300.0, 0.0)),
+    ));
+    world.spawn().insert_bundle((
+        Transform::from_xyz(500.0, 0.0, 0.0),
+        PaddleSize(Vec2::new(16.0, 64.0)),
+    ));
+
+    let mut stage = SystemStage::single(detect_paddle_collisions.system());
+    stage.run(&mut world);
+
+    let events = world.get_resource::<Events<CollisionEvent>>().unwrap();
+    let collisions: Vec<CollisionEvent> =
+        ManualEventReader::default().iter(events).copied().collect();
+    assert_eq!(collisions.len(), 1);
+    assert_eq!(collisions[0].time, 0.0);
+    assert!(!collisions[0].is_impact());
+}
+
+//Degrees between the bounce and the right paddle's face normal, positive toward the top
+fn right_paddle_bounce(hit_offset: f32) -> f32 {
+    let direction = bounce_direction(hit_offset, Vec2::new(-1.0, 0.0), Vec2::new(0.0, 1.0));
+    assert!((direction.length() - 1.0).abs() < 0.001);
+    direction.y.atan2(-direction.x).to_degrees()
+}
+
+#[test]
+fn center_hit_goes_straight_back() {
+    assert!(right_paddle_bounce(0.0).abs() < 0.001);
+}
+
+#[test]
+fn bounce_angle_follows_the_hit_offset() {
+    assert!((right_paddle_bounce(0.5) - MAX_BOUNCE_ANGLE / 2.0).abs() < 0.001);
+    assert!((right_paddle_bounce(-0.5) + MAX_BOUNCE_ANGLE / 2.0).abs() < 0.001);
+    assert!((right_paddle_bounce(1.0) - MAX_BOUNCE_ANGLE).abs() < 0.001);
+}
+
+#[test]
+fn offsets_past_the_end_are_clamped_to_the_max_angle() {
+    assert!((right_paddle_bounce(3.0) - MAX_BOUNCE_ANGLE).abs() < 0.001);
+    assert!((right_paddle_bounce(-1.5) + MAX_BOUNCE_ANGLE).abs() < 0.001);
+
+    //Even the steepest bounce still crosses the court at a quarter of its speed
+    let steepest = bounce_direction(3.0, Vec2::new(-1.0, 0.0), Vec2::new(0.0, 1.0));
+    assert!(-steepest.x > 0.25);
+}
+
+#[test]
+fn reflect_off_axis_aligned_normals_flips_one_component() {
+    let velocity = Vec2::new(300.0, -120.0);
+    assert_eq!(
+        reflect(velocity, Vec2::new(0.0, 1.0)),
+        Vec2::new(300.0, 120.0)
+    );
+    assert_eq!(
+        reflect(velocity, Vec2::new(0.0, -1.0)),
+        Vec2::new(300.0, 120.0)
+    );
+    assert_eq!(
+        reflect(velocity, Vec2::new(-1.0, 0.0)),
+        Vec2::new(-300.0, -120.0)
+    );
+}
+
+#[test]
+fn reflect_off_a_diagonal_normal_swaps_the_components() {
+    //A 45 degree surface turns straight down into straight sideways, keeping the speed
+    let normal = Vec2::new(1.0, 1.0).normalize();
+    assert!(close(
+        reflect(Vec2::new(0.0, -200.0), normal),
+        Vec2::new(200.0, 0.0)
+    ));
+    let reflected = reflect(Vec2::new(-30.0, -40.0), normal);
+    assert!(close(reflected, Vec2::new(40.0, 30.0)));
+    assert!((reflected.length() - 50.0).abs() < 0.001);
+}
+
+#[test]
+fn wall_bounce_keeps_the_rest_of_the_step() {
+    //Reaches the top wall a quarter of the way through and comes back for the other three
+    let start = Vec2::new(0.0, 342.0);
+    let velocity = Vec2::new(0.0, 40.0);
+    let (end, reflected, hit) = bounce(start, velocity, &WALLS);
+    assert!(close(end, Vec2::new(0.0, 322.0)));
+    assert_eq!(reflected, Vec2::new(0.0, -40.0));
+    assert_eq!(hit, vec![Player::Top]);
+}
+
+#[test]
+fn fast_ball_bounces_off_both_walls_in_one_step() {
+    let start = Vec2::new(0.0, 300.0);
+    let velocity = Vec2::new(0.0, 800.0);
+    let (end, reflected, hit) = bounce(start, velocity, &WALLS);
+    //52 up to the top, 704 down to the bottom, the last 44 back up
+    assert!(close(end, Vec2::new(0.0, -308.0)));
+    assert_eq!(reflected, Vec2::new(0.0, 800.0));
+    assert_eq!(hit, vec![Player::Top, Player::Bottom]);
+}
+
+#[test]
+fn corner_bounce_reflects_both_axes() {
+    //Practice walls off the right side too, this heads into the top right corner
+    let walls = [Player::Top, Player::Bottom, Player::Right];
+    let start = Vec2::new(622.0, 342.0);
+    let velocity = Vec2::new(20.0, 20.0);
+    let (end, reflected, hit) = bounce(start, velocity, &walls);
+    assert!(close(end, Vec2::new(622.0, 342.0)));
+    assert_eq!(reflected, Vec2::new(-20.0, -20.0));
+    assert_eq!(hit.len(), 2);
+}
+
+#[test]
+fn open_sides_let_the_ball_through() {
+    let start = Vec2::new(620.0, 0.0);
+    let velocity = Vec2::new(40.0, 0.0);
+    let (end, reflected, hit) = bounce(start, velocity, &WALLS);
+    assert!(close(end, Vec2::new(660.0, 0.0)));
+    assert_eq!(reflected, velocity);
+    assert!(hit.is_empty());
+}
+
+#[test]
+fn wall_sweep_after_a_paddle_hit_starts_at_the_contact() {
+    let mut builder = common::headless();
+    common::fixed_steps(&mut builder, 1);
+    let mut app = builder.app;
+    common::start_match(&mut app);
+    common::skip_serve(&mut app);
+    let mut bounds = app.world.get_resource_mut::<CourtBounds>().unwrap();
+    bounds.half_height = 80.0;
+    app.update();
+
+    //A short court with the right paddle as high as it goes. The ball meets its face 28 below
+    //the middle and leaves steeply down, fast enough to reach the bottom wall in the same step,
+    //and backing up along that new path would put the step's start past the top wall
+    let mut paddles = app.world.query::<(&Paddle, &mut Transform)>();
+    for (paddle, mut transform) in paddles.iter_mut(&mut app.world) {
+        if paddle.0 == Player::Right {
+            transform.translation.y = 48.0;
+        }
+    }
+    let (speed, time) = (20000.0, 0.37);
+    let step = speed * PHYSICS_STEP as f32;
+    let contact = Vec2::new(484.0, 20.0);
+    let mut balls = app
+        .world
+        .query_filtered::<(&mut Transform, &mut Velocity, &mut BallSpeed), With<Ball>>();
+    for (mut transform, mut velocity, mut ball_speed) in balls.iter_mut(&mut app.world) {
+        let start = contact - Vec2::new(step * time, 0.0);
+        transform.translation = start.extend(transform.translation.z);
+        velocity.0 = Vec2::new(speed, 0.0);
+        ball_speed.0 = speed;
+    }
+    app.update();
+
+    //Off at 0.875 of the full angle for the rest of the step
+    let angle = (-0.875 * 75.0_f32).to_radians();
+    let velocity = Vec2::new(-angle.cos(), angle.sin()) * speed;
+    let limit = Vec2::new(632.0, 72.0);
+    let remaining = PHYSICS_STEP as f32 * (1.0 - time);
+    let (expected, _, hit) = sweep_walls(contact, velocity, remaining, limit, &WALLS);
+    assert_eq!(hit, vec![Player::Bottom]);
+    let mut balls = app.world.query_filtered::<&Transform, With<Ball>>();
+    let position = balls
+        .iter(&app.world)
+        .next()
+        .unwrap()
+        .translation
+        .truncate();
+    assert!((position - expected).length() < 0.01);
+
+    let events = app.world.get_resource::<Events<WallHitEvent>>().unwrap();
+    let mut reader = ManualEventReader::<WallHitEvent>::default();
+    let sides: Vec<WallSide> = reader.iter(events).map(|hit| hit.side).collect();
+    assert_eq!(sides, vec![WallSide::Bottom]);
+}
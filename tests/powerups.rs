@@ -0,0 +1,20 @@
+use bevy_pong::gameplay::Player;
+use bevy_pong::physics::CourtBounds;
+use bevy_pong::powerups::{shield_position, SHIELD_WIDTH};
+use bevy_pong::settings::Settings;
+
+#[test]
+fn shield_stands_in_front_of_the_paddle_face() {
+    let settings = Settings::default();
+    let bounds = CourtBounds::default();
+
+    for &player in [Player::Left, Player::Right].iter() {
+        let paddle = bounds.paddle_position(player, settings.paddle.edge_offset);
+        let shield = shield_position(&bounds, &settings, player);
+        //Measured from the paddle's center toward the middle of the court
+        let into_court = -player.goal_normal();
+        let gap = (shield - paddle).dot(into_court) - settings.paddle.width / 2.0;
+        assert!((gap - SHIELD_WIDTH).abs() < 0.001);
+        assert_eq!(shield.dot(player.axis()), 0.0);
+    }
+}
@@ -0,0 +1,34 @@
+use bevy::prelude::*;
+use bevy_pong::gameplay::{GameMode, Score};
+use bevy_pong::recording::{Playback, Recording, Session};
+use bevy_pong::GameState;
+use std::path::Path;
+
+//A three point match between two keyboard players, with power-ups and early serves
+const RECORDING: &str = "tests/recordings/match.json";
+
+#[test]
+fn replay_matches_recorded_score() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(RECORDING);
+    let recording = Recording::load(&path).unwrap();
+    let expected = recording.score.clone();
+    let frames = recording.frames.len();
+    let mut app = bevy_pong::build_app(true, Session::Replay(Box::new(recording))).app;
+
+    //The recorded steps replace real time, so this runs as fast as the frames can be stepped
+    for _ in 0..=frames {
+        app.update();
+    }
+    assert!(app.world.get_resource::<Playback>().unwrap().finished());
+
+    let score = app.world.get_resource::<Score>().unwrap();
+    let mode = app.world.get_resource::<GameMode>().unwrap();
+    let replayed: Vec<u32> = mode
+        .players()
+        .iter()
+        .map(|player| score.points(*player))
+        .collect();
+    assert_eq!(replayed, expected);
+    let state = app.world.get_resource::<State<GameState>>().unwrap();
+    assert_eq!(*state.current(), GameState::GameOver);
+}
@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+use bevy_pong::gameplay::{GameMode, MatchConfig, MatchTimer, Player, Score, SetScore};
+use bevy_pong::physics::{Ball, Velocity};
+use bevy_pong::settings::{MatchSettings, Settings};
+use bevy_pong::GameState;
+use common::state;
+
+mod common;
+
+//Points are handed out directly, nothing here needs the ball to actually score
+fn point(app: &mut App, left: bool) {
+    let mut score = app.world.get_resource_mut::<Score>().unwrap();
+    if left {
+        score.left += 1;
+    } else {
+        score.right += 1;
+    }
+    app.update();
+}
+
+//Best of three sets of two against a three second clock, the ball held still so only handed out
+//points count
+fn timed_sets_match() -> App {
+    let mut builder = common::headless();
+    common::fixed_steps(&mut builder, 24);
+    builder.insert_resource(Settings {
+        rules: MatchSettings {
+            points_to_win: 2,
+            sets_to_win: 2,
+            time_limit: 0.05,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let mut app = builder.app;
+    common::start_match(&mut app);
+    common::skip_serve(&mut app);
+    let mut balls = app.world.query_filtered::<&mut Velocity, With<Ball>>();
+    for mut velocity in balls.iter_mut(&mut app.world) {
+        velocity.0 = Vec2::ZERO;
+    }
+    app
+}
+
+//A fifth of a second a frame, well past the clock
+fn run_out_the_clock(app: &mut App) {
+    for _ in 0..20 {
+        app.update();
+    }
+}
+
+#[test]
+fn best_of_three_sets_of_two() {
+    let mut builder = common::headless();
+    builder.insert_resource(Settings {
+        rules: MatchSettings {
+            points_to_win: 2,
+            sets_to_win: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let mut app = builder.app;
+    common::start_match(&mut app);
+
+    //Taking a set starts the points over
+    point(&mut app, true);
+    point(&mut app, false);
+    point(&mut app, true);
+    let score = app.world.get_resource::<Score>().unwrap();
+    assert_eq!((score.left, score.right), (0, 0));
+    assert_eq!(state(&app), GameState::Playing);
+
+    point(&mut app, false);
+    point(&mut app, false);
+    assert_eq!(state(&app), GameState::Playing);
+
+    //The deciding set
+    point(&mut app, true);
+    point(&mut app, false);
+    assert_eq!(state(&app), GameState::Playing);
+    point(&mut app, true);
+    assert_eq!(state(&app), GameState::GameOver);
+
+    let sets = app.world.get_resource::<SetScore>().unwrap();
+    assert_eq!((sets.left_sets, sets.right_sets), (2, 1));
+    assert_eq!(sets.sets, vec![(2, 1), (0, 2), (2, 1)]);
+}
+
+#[test]
+fn time_up_goes_to_whoever_leads_on_sets() {
+    let mut app = timed_sets_match();
+    point(&mut app, true);
+    point(&mut app, true);
+
+    //Ahead in the set being played isn't enough when behind on sets
+    point(&mut app, false);
+    run_out_the_clock(&mut app);
+    assert_eq!(state(&app), GameState::GameOver);
+    let sets = app.world.get_resource::<SetScore>().unwrap();
+    assert_eq!((sets.left_sets, sets.right_sets), (1, 0));
+    assert_eq!(sets.sets, vec![(2, 0)]);
+    let config = app.world.get_resource::<MatchConfig>().unwrap();
+    let score = app.world.get_resource::<Score>().unwrap();
+    let winner = config.winner(GameMode::TwoPlayer, score, sets);
+    assert_eq!(winner, Player::Left);
+}
+
+#[test]
+fn level_on_sets_at_time_is_settled_in_the_set_being_played() {
+    let mut app = timed_sets_match();
+    point(&mut app, true);
+    point(&mut app, true);
+    point(&mut app, true);
+    point(&mut app, false);
+    point(&mut app, false);
+
+    //Level on points too, so the next goal takes the set and the match
+    point(&mut app, true);
+    point(&mut app, false);
+    run_out_the_clock(&mut app);
+    assert!(app.world.get_resource::<MatchTimer>().unwrap().sudden_death);
+    assert_eq!(state(&app), GameState::Playing);
+
+    point(&mut app, false);
+    assert_eq!(state(&app), GameState::GameOver);
+    let sets = app.world.get_resource::<SetScore>().unwrap();
+    assert_eq!((sets.left_sets, sets.right_sets), (1, 2));
+    assert_eq!(sets.sets, vec![(2, 0), (1, 2), (1, 2)]);
+}
@@ -0,0 +1,55 @@
+use bevy::prelude::*;
+use bevy_pong::gameplay::Player;
+use bevy_pong::physics::{Ball, BallSpeed, Paddle, StickHold, Sticky, StuckTo, Velocity};
+
+mod common;
+
+fn stuck(app: &mut App) -> Option<Entity> {
+    let mut balls = app.world.query_filtered::<&StuckTo, With<Ball>>();
+    balls.iter(&app.world).next().map(|stuck| stuck.0)
+}
+
+#[test]
+fn sticky_paddle_holds_the_ball_then_launches_it() {
+    let mut builder = common::headless();
+    common::fixed_steps(&mut builder, 1);
+    let mut app = builder.app;
+    common::start_match(&mut app);
+    common::skip_serve(&mut app);
+
+    //Straight at the middle of the right paddle, as if its player had picked up the power-up
+    let mut paddles = app.world.query::<(Entity, &Paddle)>();
+    let right = paddles
+        .iter(&app.world)
+        .find(|(_, paddle)| paddle.0 == Player::Right)
+        .map(|(entity, _)| entity)
+        .unwrap();
+    app.world.entity_mut(right).insert(Sticky);
+    let mut balls = app
+        .world
+        .query_filtered::<(&mut Transform, &mut Velocity, &mut BallSpeed), With<Ball>>();
+    for (mut transform, mut velocity, mut speed) in balls.iter_mut(&mut app.world) {
+        transform.translation = Vec3::new(300.0, 0.0, transform.translation.z);
+        velocity.0 = Vec2::new(400.0, 0.0);
+        speed.0 = 400.0;
+    }
+
+    //Half a second is plenty to reach the paddle
+    for _ in 0..60 {
+        app.update();
+    }
+    assert_eq!(stuck(&mut app), Some(right));
+    let mut balls = app.world.query_filtered::<&Velocity, With<Ball>>();
+    assert!(balls
+        .iter(&app.world)
+        .all(|velocity| velocity.0 == Vec2::ZERO));
+
+    //Nobody serves it, so it goes by itself once the hold runs out
+    let frames = (StickHold::SECONDS * 120.0) as usize + 1;
+    for _ in 0..frames {
+        app.update();
+    }
+    assert_eq!(stuck(&mut app), None);
+    let mut balls = app.world.query_filtered::<&Velocity, With<Ball>>();
+    assert!(balls.iter(&app.world).all(|velocity| velocity.0.x < 0.0));
+}
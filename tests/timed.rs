@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+use bevy_pong::gameplay::{MatchTimer, Score};
+use bevy_pong::physics::{Ball, CourtBounds, Velocity, PHYSICS_STEP};
+use bevy_pong::settings::{MatchSettings, Settings};
+use bevy_pong::GameState;
+
+mod common;
+
+//A fifth of a second of game time every frame
+const STEPS_PER_FRAME: u32 = 24;
+
+fn run_for(app: &mut App, seconds: f64) {
+    let frames = (seconds / PHYSICS_STEP / STEPS_PER_FRAME as f64).ceil() as usize;
+    for _ in 0..frames {
+        app.update();
+    }
+}
+
+//A three second match with the ball held still in the middle, so nobody ever scores
+fn timed_match(settings: Settings) -> App {
+    let mut builder = common::headless();
+    common::fixed_steps(&mut builder, STEPS_PER_FRAME);
+    builder.insert_resource(Settings {
+        rules: MatchSettings {
+            time_limit: 0.05,
+            ..Default::default()
+        },
+        ..settings
+    });
+    let mut app = builder.app;
+    common::start_match(&mut app);
+    common::skip_serve(&mut app);
+    let mut balls = app.world.query_filtered::<&mut Velocity, With<Ball>>();
+    for mut velocity in balls.iter_mut(&mut app.world) {
+        velocity.0 = Vec2::ZERO;
+    }
+    app
+}
+
+fn half_size(app: &App) -> Vec2 {
+    app.world.get_resource::<CourtBounds>().unwrap().half_size()
+}
+
+#[test]
+fn level_at_time_goes_to_sudden_death() {
+    let mut app = timed_match(Settings::default());
+
+    //Paused time doesn't count
+    app.world
+        .get_resource_mut::<State<GameState>>()
+        .unwrap()
+        .push(GameState::Paused)
+        .unwrap();
+    run_for(&mut app, 5.0);
+    let remaining = app.world.get_resource::<MatchTimer>().unwrap().remaining();
+    assert!(remaining.unwrap().as_secs_f32() > 2.5);
+    app.world
+        .get_resource_mut::<State<GameState>>()
+        .unwrap()
+        .pop()
+        .unwrap();
+
+    run_for(&mut app, 4.0);
+    assert!(app.world.get_resource::<MatchTimer>().unwrap().sudden_death);
+    assert_eq!(half_size(&app), Vec2::new(640.0, 360.0));
+
+    //Fifteen seconds in, the court closes in by a tenth
+    run_for(&mut app, 15.0);
+    assert!((half_size(&app) - Vec2::new(576.0, 324.0)).length() < 0.01);
+
+    //The next goal ends it, and the court is back to full size for the next match
+    app.world.get_resource_mut::<Score>().unwrap().left += 1;
+    app.update();
+    assert_eq!(common::state(&app), GameState::GameOver);
+    app.update();
+    assert_eq!(half_size(&app), Vec2::new(640.0, 360.0));
+}
+
+#[test]
+fn court_never_shrinks_past_the_paddles() {
+    //Big paddles far from the edge reach the limit in a few shrinks
+    let mut settings = Settings::default();
+    settings.paddle.height = 400.0;
+    settings.paddle.edge_offset = 300.0;
+    let mut app = timed_match(settings.clone());
+    run_for(&mut app, 3.0 + 15.0 * 10.0);
+
+    //Room for the paddle and a ball, with each paddle still its distance from the edge
+    let diameter = settings.ball.radius * 2.0;
+    let height = (settings.paddle.height + diameter) / 2.0;
+    let width = settings.paddle.edge_offset + settings.paddle.width / 2.0 + diameter;
+    assert_eq!(half_size(&app), Vec2::new(width, height));
+    assert_eq!(common::state(&app), GameState::Playing);
+}